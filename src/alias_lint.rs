@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use crate::name_normalizer::{clean_name, Alias};
+
+/// A suspicious line of the alias file, lines starting at 1.
+#[derive(Debug, PartialEq)]
+pub enum AliasIssue {
+    /// The same name is aliased on several lines, the last one being used
+    DuplicateSource { source: String, lines: Vec<usize> },
+    /// Following the aliases comes back to the first name
+    Cycle { names: Vec<String>, line: usize },
+    /// The target is itself aliased, while a name is only aliased once
+    Chain { source: String, target: String, next_target: String, line: usize },
+    /// The source is not written as a normalized name, so it never matches
+    NotNormalized { source: String, normalized: String, line: usize },
+    /// The target is not a station of the loaded price grids
+    UnknownTarget { target: String, line: usize }
+}
+
+impl AliasIssue {
+    pub fn line(&self) -> usize {
+        match self {
+            AliasIssue::DuplicateSource { lines, .. } => lines.first().copied().unwrap_or_default(),
+            AliasIssue::Cycle { line, .. } => *line,
+            AliasIssue::Chain { line, .. } => *line,
+            AliasIssue::NotNormalized { line, .. } => *line,
+            AliasIssue::UnknownTarget { line, .. } => *line
+        }
+    }
+}
+
+impl fmt::Display for AliasIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.line())?;
+        match self {
+            AliasIssue::DuplicateSource { source, lines } => write!(f, "{} is aliased on lines {:?}", source, lines),
+            AliasIssue::Cycle { names, .. } => write!(f, "cycle {} -> {}", names.join(" -> "), names[0]),
+            AliasIssue::Chain { source, target, next_target, .. } => write!(f, "{} -> {} is aliased again to {}", source, target, next_target),
+            AliasIssue::NotNormalized { source, normalized, .. } => write!(f, "{} is normalized as {} and never matches", source, normalized),
+            AliasIssue::UnknownTarget { target, .. } => write!(f, "{} is not in any price grid", target)
+        }
+    }
+}
+
+/// Check the aliases against themselves and against the stations of the price grids.
+pub fn lint_aliases(aliases: &[Alias], station_names: &BTreeSet<String>) -> Vec<AliasIssue> {
+    let mut issues = Vec::new();
+    let mut lines_by_source: BTreeMap<(Option<String>, &str), Vec<usize>> = BTreeMap::new();
+    for alias in aliases {
+        lines_by_source.entry((scope_key(alias), &alias.source)).or_default().push(alias.line);
+    }
+    for ((_, source), lines) in &lines_by_source {
+        if lines.len() > 1 {
+            issues.push(AliasIssue::DuplicateSource {
+                source: source.to_string(),
+                lines: lines.clone()
+            });
+        }
+    }
+
+    // the last alias of a name is the one used
+    let targets = aliases.iter()
+        .map(|alias| ((scope_key(alias), alias.source.as_str()), alias.target.as_str()))
+        .collect::<Targets>();
+    let mut cycles: BTreeSet<Vec<String>> = BTreeSet::new();
+    for alias in aliases {
+        let normalized = clean_name(&alias.source);
+        if normalized != alias.source {
+            issues.push(AliasIssue::NotNormalized {
+                source: alias.source.to_string(),
+                normalized,
+                line: alias.line
+            });
+        }
+        let scope = scope_key(alias);
+        if let Some(cycle) = cycle_from(&scope, &alias.source, &targets) {
+            // a cycle is reported once, from its smallest name
+            let mut sorted_cycle = cycle.clone();
+            sorted_cycle.sort();
+            if cycles.insert(sorted_cycle) {
+                issues.push(AliasIssue::Cycle {
+                    names: cycle,
+                    line: alias.line
+                });
+            }
+        } else if let Some(next_target) = target(&targets, &scope, &alias.target).filter(|next_target| *next_target != alias.target) {
+            issues.push(AliasIssue::Chain {
+                source: alias.source.to_string(),
+                target: alias.target.to_string(),
+                next_target: next_target.to_string(),
+                line: alias.line
+            });
+        }
+        if !station_names.contains(&alias.target) {
+            issues.push(AliasIssue::UnknownTarget {
+                target: alias.target.to_string(),
+                line: alias.line
+            });
+        }
+    }
+    issues.sort_by_key(AliasIssue::line);
+    issues
+}
+
+/// The target of each alias, by uppercase scope and source.
+type Targets<'a> = HashMap<(Option<String>, &'a str), &'a str>;
+
+fn scope_key(alias: &Alias) -> Option<String> {
+    alias.scope.as_ref().map(|scope| scope.to_uppercase())
+}
+
+/// The target of a name in a scope, else its global target.
+fn target<'a>(targets: &Targets<'a>, scope: &Option<String>, source: &str) -> Option<&'a str> {
+    targets.get(&(scope.clone(), source))
+        .or_else(|| targets.get(&(None, source)))
+        .copied()
+}
+
+/// The names of the cycle starting at a name, if following its aliases comes back to it.
+fn cycle_from(scope: &Option<String>, source: &str, targets: &Targets) -> Option<Vec<String>> {
+    let mut names = vec![source.to_string()];
+    let mut name = target(targets, scope, source)?;
+    while name != source {
+        if names.iter().any(|seen| seen == name) {
+            // a cycle not going through the source
+            return None;
+        }
+        names.push(name.to_string());
+        name = target(targets, scope, name)?;
+    }
+    // a name aliased to itself is harmless
+    Some(names).filter(|names| names.len() > 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use crate::name_normalizer::Alias;
+    use super::{lint_aliases, AliasIssue};
+
+    fn alias(source: &str, target: &str, line: usize) -> Alias {
+        Alias {
+            source: source.to_string(),
+            target: target.to_string(),
+            scope: None,
+            line
+        }
+    }
+
+    #[test]
+    fn lint() {
+        let aliases = [
+            alias("A", "B", 1),
+            alias("B", "A", 2),
+            alias("C", "D", 3),
+            alias("D", "E", 4),
+            alias("C", "E", 5),
+            alias("Tulle-Nord", "E", 6)
+        ];
+        let station_names = BTreeSet::from(["E".to_string()]);
+        let issues = lint_aliases(&aliases, &station_names);
+        assert!(issues.contains(&AliasIssue::Cycle { names: vec!["A".to_string(), "B".to_string()], line: 1 }));
+        assert!(issues.contains(&AliasIssue::DuplicateSource { source: "C".to_string(), lines: vec![3, 5] }));
+        assert!(issues.contains(&AliasIssue::Chain { source: "C".to_string(), target: "D".to_string(), next_target: "E".to_string(), line: 3 }));
+        assert!(issues.contains(&AliasIssue::NotNormalized { source: "Tulle-Nord".to_string(), normalized: "TULLE NORD".to_string(), line: 6 }));
+        assert!(issues.contains(&AliasIssue::UnknownTarget { target: "D".to_string(), line: 3 }));
+        assert_eq!(1, issues.iter().filter(|issue| matches!(issue, AliasIssue::Cycle { .. })).count());
+    }
+}
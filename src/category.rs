@@ -1,16 +1,56 @@
-use enum_iterator::{Sequence};
+use enum_iterator::{all, Sequence};
+use serde::Serialize;
 
-#[derive(PartialEq, Eq, Hash, Copy, Clone, Sequence)]
-pub(crate) enum Category {
+/// Vehicle category, following the French toll classes.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, PartialOrd, Ord, Debug, Sequence, Serialize)]
+pub enum Category {
     Car,
+    Intermediate,
+    TwoAxleTruck,
+    ThreeAxleTruck,
     Motorcycle
 }
 
-impl std::fmt::Display for Category {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Category {
+    /// Parse a category from its name (case insensitive) or its class number.
+    pub fn from_name(name: &str) -> Option<Category> {
+        let name = name.trim();
+        all::<Category>().find(|category| category.to_string().eq_ignore_ascii_case(name) || category.class().to_string() == name)
+    }
+
+    /// French vehicle class (1 to 5) as printed in the operators' price grids.
+    pub fn class(&self) -> u8 {
         match self {
-            Category::Car => write!(f, "Car"),
-            Category::Motorcycle => write!(f, "Motorcycle")
+            Category::Car => 1,
+            Category::Intermediate => 2,
+            Category::TwoAxleTruck => 3,
+            Category::ThreeAxleTruck => 4,
+            Category::Motorcycle => 5
         }
     }
-}
\ No newline at end of file
+
+    /// Waze vehicle types the matrix of this category applies to.
+    pub fn limit_to_vehicles(&self) -> Vec<String> {
+        let vehicles: &[&str] = match self {
+            Category::Car => &["PRIVATE", "TAXI", "EV"],
+            Category::Intermediate => &["RV"],
+            Category::TwoAxleTruck => &["TRUCK"],
+            Category::ThreeAxleTruck => &["HEAVY_TRUCK"],
+            Category::Motorcycle => &["MOTORCYCLE"]
+        };
+        vehicles.iter().map(|vehicle| vehicle.to_string()).collect()
+    }
+}
+
+impl std::fmt::Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Category::Car => "Car",
+            Category::Intermediate => "Intermediate",
+            Category::TwoAxleTruck => "TwoAxleTruck",
+            Category::ThreeAxleTruck => "ThreeAxleTruck",
+            Category::Motorcycle => "Motorcycle"
+        };
+        f.pad(name)
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::path::Path;
+use crate::money::{Currency, Money};
+
+/// Rates converting the prices of a currency to another, from a csv file with a `from,to,rate` header
+/// where `CHF,EUR,1.05` converts 1 CHF to 1.05 EUR. A rate also converts back with its inverse.
+#[derive(Default, Debug)]
+pub struct ExchangeRates {
+    rates: HashMap<(Currency, Currency), f64>
+}
+
+impl ExchangeRates {
+    /// Load the rates of the file, none without file.
+    pub fn load(path: &Path) -> Result<ExchangeRates, String> {
+        let mut exchange_rates = ExchangeRates::default();
+        if !path.is_file() {
+            return Ok(exchange_rates);
+        }
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .comment(Some(b'#'))
+            .from_path(path)
+            .map_err(|error| format!("Unable to read {} : {}", path.display(), error))?;
+        for record in reader.records() {
+            let record = record.map_err(|error| format!("Invalid exchange rate in {} : {}", path.display(), error))?;
+            let line = record.position().map(|position| position.line()).unwrap_or_default();
+            let invalid = || format!("Invalid exchange rate in {} line {}", path.display(), line);
+            let from = record.get(0).and_then(Currency::new).ok_or_else(invalid)?;
+            let to = record.get(1).and_then(Currency::new).ok_or_else(invalid)?;
+            let rate = record.get(2)
+                .and_then(|rate| rate.parse::<f64>().ok())
+                .filter(|rate| rate.is_finite() && *rate > 0.0)
+                .ok_or_else(invalid)?;
+            exchange_rates.add(from, to, rate);
+        }
+        Ok(exchange_rates)
+    }
+
+    pub fn add(&mut self, from: Currency, to: Currency, rate: f64) {
+        self.rates.insert((from, to), rate);
+    }
+
+    /// The rate converting a currency to another, declared or the inverse of the reverse rate.
+    pub fn rate(&self, from: Currency, to: Currency) -> Option<f64> {
+        if from == to {
+            return Some(1.0);
+        }
+        self.rates.get(&(from, to)).copied()
+            .or_else(|| self.rates.get(&(to, from)).map(|rate| 1.0 / rate))
+    }
+
+    /// The amount in another currency, rounded to the hundredth half away from zero, None without rate.
+    pub fn convert(&self, money: Money, to: Currency) -> Option<Money> {
+        if money.currency() == to {
+            return Some(money);
+        }
+        let rate = self.rate(money.currency(), to)?;
+        Some(Money::new((money.minor() as f64 * rate).round() as i64, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::money::{Currency, Money};
+    use super::ExchangeRates;
+
+    #[test]
+    fn convert() {
+        let chf = Currency::new("CHF").unwrap();
+        let mut exchange_rates = ExchangeRates::default();
+        exchange_rates.add(chf, Currency::EUR, 1.05);
+        assert_eq!(Some(Money::new(4568, Currency::EUR)), exchange_rates.convert(Money::new(4350, chf), Currency::EUR));
+        assert_eq!(Some(Money::new(100, chf)), exchange_rates.convert(Money::new(105, Currency::EUR), chf));
+        assert_eq!(None, exchange_rates.convert(Money::new(100, Currency::new("GBP").unwrap()), Currency::EUR));
+    }
+}
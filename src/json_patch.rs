@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Locate the text of the given fields of every toll of a toll file, so that they can be replaced
+/// without touching the rest of the file. A field missing from a toll has no range.
+pub(crate) fn toll_field_ranges(text: &str, fields: &[&str]) -> Result<Vec<HashMap<String, Range<usize>>>, String> {
+    let mut scanner = JsonScanner {
+        text: text.as_bytes(),
+        position: 0
+    };
+    let mut tolls = Vec::new();
+    scanner.object(|scanner, key| {
+        if key != "tolls" {
+            return scanner.skip_value();
+        }
+        scanner.array(|scanner| {
+            let mut ranges = HashMap::new();
+            scanner.object(|scanner, key| {
+                let start = scanner.position;
+                scanner.skip_value()?;
+                if fields.contains(&key) {
+                    ranges.insert(key.to_string(), start..scanner.position);
+                }
+                Ok(())
+            })?;
+            tolls.push(ranges);
+            Ok(())
+        })
+    })?;
+    Ok(tolls)
+}
+
+/// The indentation of the file, None when it is written on a single line.
+pub(crate) fn indentation(text: &str) -> Option<String> {
+    text.lines()
+        .skip(1)
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .find(|indent| !indent.is_empty())
+        .map(str::to_string)
+}
+
+/// Re-indent a pretty printed value to be written after a key of the given line.
+pub(crate) fn indent_value(value: &str, line_indent: &str) -> String {
+    value.lines()
+        .enumerate()
+        .map(|(index, line)| if index == 0 { line.to_string() } else { format!("{}{}", line_indent, line) })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// The leading whitespace of the line containing the given position.
+pub(crate) fn line_indent(text: &str, position: usize) -> &str {
+    let line_start = text[..position].rfind('\n').map(|index| index + 1).unwrap_or(0);
+    let line = &text[line_start..position];
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Walk a json text already known to be valid, only keeping track of the positions.
+struct JsonScanner<'a> {
+    text: &'a [u8],
+    position: usize
+}
+
+impl<'a> JsonScanner<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.position < self.text.len() && self.text[self.position].is_ascii_whitespace() {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.text.get(self.position) != Some(&expected) {
+            return Err(format!("expected '{}' at byte {}", expected as char, self.position));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    /// Whether the next character closes the current object or array, consuming it.
+    fn close(&mut self, closing: u8) -> bool {
+        self.skip_whitespace();
+        if self.text.get(self.position) == Some(&closing) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn object(&mut self, mut value: impl FnMut(&mut Self, &str) -> Result<(), String>) -> Result<(), String> {
+        self.expect(b'{')?;
+        if self.close(b'}') {
+            return Ok(());
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.expect(b':')?;
+            self.skip_whitespace();
+            value(self, &key)?;
+            if self.close(b'}') {
+                return Ok(());
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    fn array(&mut self, mut element: impl FnMut(&mut Self) -> Result<(), String>) -> Result<(), String> {
+        self.expect(b'[')?;
+        if self.close(b']') {
+            return Ok(());
+        }
+        loop {
+            self.skip_whitespace();
+            element(self)?;
+            if self.close(b']') {
+                return Ok(());
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let start = self.position;
+        while self.position < self.text.len() {
+            match self.text[self.position] {
+                b'\\' => self.position += 2,
+                b'"' => {
+                    let string = String::from_utf8_lossy(&self.text[start..self.position]).to_string();
+                    self.position += 1;
+                    return Ok(string);
+                }
+                _ => self.position += 1
+            }
+        }
+        Err(format!("unterminated string at byte {}", start))
+    }
+
+    fn skip_value(&mut self) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.text.get(self.position) {
+            Some(b'{') => self.object(|scanner, _| scanner.skip_value()),
+            Some(b'[') => self.array(|scanner| scanner.skip_value()),
+            Some(b'"') => self.string().map(|_| ()),
+            Some(_) => {
+                let start = self.position;
+                while self.position < self.text.len() && !b",]} \t\r\n".contains(&self.text[self.position]) {
+                    self.position += 1;
+                }
+                if self.position == start {
+                    return Err(format!("unexpected character at byte {}", start));
+                }
+                Ok(())
+            }
+            None => Err("unexpected end of file".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{indentation, toll_field_ranges};
+
+    #[test]
+    fn locate_matrices() {
+        let text = "{\n  \"version\": 1.50,\n  \"tolls\": [\n    {\"toll_id\": \"A\", \"entry_exit_matrix\": [ [0.0] ], \"note\": \"]\"},\n    {\"toll_id\": \"B\"}\n  ]\n}";
+        let ranges = toll_field_ranges(text, &["entry_exit_matrix"]).unwrap();
+        assert_eq!(2, ranges.len());
+        assert_eq!("[ [0.0] ]", &text[ranges[0]["entry_exit_matrix"].clone()]);
+        assert!(ranges[1].is_empty());
+        assert_eq!(Some("  ".to_string()), indentation(text));
+    }
+}
@@ -0,0 +1,30 @@
+//! Build the price matrices of Waze toll files from the price grids published by the toll operators.
+//!
+//! [`price_service::PriceService`] loads the price grids described by [`price_paths::PricePaths`],
+//! answers price lookups and fills the `entry_exit_matrix` of the tolls of a [`toll_file::TollFile`].
+
+pub mod alias_lint;
+pub mod category;
+pub mod exchange_rate;
+pub mod matrix_audit;
+pub mod matrix_diff;
+pub mod money;
+pub mod name_normalizer;
+pub mod price;
+pub mod price_consistency;
+pub mod price_evolution;
+pub mod price_grid;
+pub mod price_paths;
+pub mod price_repl;
+pub mod price_server;
+pub mod price_service;
+pub mod report;
+pub mod toll_batch;
+pub mod toll_file;
+mod io_tools;
+mod json_patch;
+#[cfg(test)]
+mod test_prices;
+
+/// Year of the price files whose name does not start with a year.
+pub const DEFAULT_YEAR: u16 = 2019;
@@ -1,59 +1,565 @@
-extern crate core;
-
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use price_service::PriceService;
-
-mod price_grid;
-mod io_tools;
-mod toll_file;
-mod name_normalizer;
-mod price_service;
-mod price;
-mod category;
+use chrono::{NaiveDate, Utc};
+use enum_iterator::all;
+use serde::Serialize;
+use waze_toll_tool::alias_lint::lint_aliases;
+use waze_toll_tool::category::Category;
+use waze_toll_tool::matrix_audit::TollAudit;
+use waze_toll_tool::name_normalizer::NameNormalizer;
+use waze_toll_tool::price_consistency::ConsistencyOptions;
+use waze_toll_tool::price_grid::PriceKey;
+use waze_toll_tool::price_paths::PricePaths;
+use waze_toll_tool::price_repl::PriceRepl;
+use waze_toll_tool::price_server::PriceServer;
+use waze_toll_tool::price_service::{MatrixOptions, MissingPricePolicy, PriceService};
+use waze_toll_tool::report::Report;
+use waze_toll_tool::toll_batch::{toll_files, TollOutput};
+use waze_toll_tool::toll_file::{load_toll_file, TollFile};
 
-pub(crate) const DEFAULT_YEAR: u16 = 2019;
 const USAGE: u8 = 64;
 
 fn usage() -> ExitCode {
-    println!("waze-toll-tool build-matrix <toll-file.json>");
-    println!("waze-toll-tool get-prices <entry_name>");
-    println!("waze-toll-tool check-prices");
+    println!("waze-toll-tool build-matrix <toll-file.json|directory|pattern> [--date <yyyy-mm-dd>] [--infer] [--missing <fail|keep|zero|value>]");
+    println!("                            [--output <file|directory> | --in-place | --dry-run] [--min-coverage <percent>] [--report <report.json>]");
+    println!("waze-toll-tool diff-matrix <toll-file.json> [--date <yyyy-mm-dd>] [--infer] [--missing <fail|keep|zero|value>] [--json <diff.json>]");
+    println!("waze-toll-tool get-prices <entry_name> [--date <yyyy-mm-dd>]");
+    println!("waze-toll-tool get-station <station_name>");
+    println!("waze-toll-tool get-plaza <plaza_name> [--date <yyyy-mm-dd>] [--network <network>]");
+    println!("waze-toll-tool get-history <entry_name> <exit_name> [--network <network>]");
+    println!("waze-toll-tool add-alias <section_name> [<station_name>]");
+    println!("waze-toll-tool lint-aliases");
+    println!("waze-toll-tool check-prices [--conflicts] [--report <report.json>]");
+    println!("waze-toll-tool check-consistency [<toll-file.json|directory|pattern>] [--date <yyyy-mm-dd>] [--detour <ratio>] [--outlier <ratio>]");
+    println!("                                 [--max-change <percent>] [--json <issues.json>]");
+    println!("waze-toll-tool price-evolution [--operator <operator>] [--json <evolution.json>]");
+    println!("waze-toll-tool serve [--address <host:port>]");
+    println!("waze-toll-tool interactive [--date <yyyy-mm-dd>]");
+    println!();
+    println!("Every command accepts --prices <directory> to load the prices from another directory than ./prices");
     ExitCode::from(USAGE)
 }
 
-fn command_build_matrix(args: &Vec<String>) -> ExitCode {
+fn option_value<'a>(args: &'a [String], option: &str) -> Option<&'a String> {
+    args.iter()
+        .position(|arg| arg == option)
+        .and_then(|index| args.get(index + 1))
+}
+
+/// The date given by the --date option, today by default.
+fn date_option(args: &[String]) -> Result<NaiveDate, String> {
+    match option_value(args, "--date") {
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|error| format!("Invalid date {} : {}", date, error)),
+        None => Ok(Utc::now().date_naive())
+    }
+}
+
+/// The options of the commands building matrices: --date, --infer and --missing.
+fn matrix_options(args: &[String]) -> Result<MatrixOptions, String> {
+    let mut options = MatrixOptions::new(date_option(args)?);
+    options.infer_missing = args.iter().any(|arg| arg == "--infer");
+    if let Some(policy) = option_value(args, "--missing") {
+        options.missing_price = MissingPricePolicy::from_name(policy).ok_or(format!("Invalid missing price policy {}", policy))?;
+    }
+    Ok(options)
+}
+
+/// The number given by an option, if any.
+fn number_option(args: &[String], option: &str) -> Result<Option<f64>, String> {
+    option_value(args, option)
+        .map(|value| value.parse::<f64>().map_err(|error| format!("Invalid {} {} : {}", option, value, error)))
+        .transpose()
+}
+
+/// The price paths of the --prices option, ./prices by default.
+fn price_paths(args: &[String]) -> PricePaths {
+    match option_value(args, "--prices") {
+        Some(directory) => PricePaths::new(directory),
+        None => PricePaths::default()
+    }
+}
+
+fn load_price_service(args: &[String]) -> Result<PriceService, ExitCode> {
+    PriceService::new(&price_paths(args)).map_err(|error| {
+        eprintln!("Unable to load the prices : {}", error);
+        ExitCode::FAILURE
+    })
+}
+
+/// Write the json report when the --report option is given.
+fn write_report(args: &[String], price_service: &PriceService, tolls: &[TollAudit]) -> ExitCode {
+    if let Some(report_file) = option_value(args, "--report") {
+        let report = Report {
+            load: price_service.load_audit(),
+            tolls
+        };
+        if let Err(error) = report.write(Path::new(report_file)) {
+            eprintln!("Unable to write report {} : {}", report_file, error);
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Write a value as pretty json to the file of the --json option, if any.
+fn write_json<T: Serialize>(args: &[String], value: &T) -> ExitCode {
+    if let Some(json_file) = option_value(args, "--json") {
+        let written = serde_json::to_string_pretty(value)
+            .map_err(std::io::Error::from)
+            .and_then(|json| std::fs::write(json_file, json));
+        if let Err(error) = written {
+            eprintln!("Unable to write {} : {}", json_file, error);
+            return ExitCode::FAILURE;
+        }
+        println!("Wrote {}", json_file);
+    }
+    ExitCode::SUCCESS
+}
+
+fn command_build_matrix(args: &[String]) -> ExitCode {
     if args.len() < 3 {
         return usage();
     }
-    let toll_file = &args[2];
-    let price_service = PriceService::new();
-    price_service.build_matrix(toll_file);
-    ExitCode::SUCCESS
+    let toll_files = match toll_files(&args[2]) {
+        Ok(toll_files) => toll_files,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    let options = match matrix_options(args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            return usage();
+        }
+    };
+    let output = match (option_value(args, "--output"), args.iter().any(|arg| arg == "--in-place")) {
+        (Some(_), true) => {
+            eprintln!("--output and --in-place cannot be used together");
+            return usage();
+        }
+        (Some(output), false) => TollOutput::Path(PathBuf::from(output)),
+        (None, true) => TollOutput::InPlace,
+        (None, false) => TollOutput::Default
+    };
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let min_coverage = match option_value(args, "--min-coverage").map(|percent| percent.parse::<f64>()) {
+        Some(Ok(percent)) => Some(percent),
+        Some(Err(error)) => {
+            eprintln!("Invalid minimum coverage : {}", error);
+            return usage();
+        }
+        None => None
+    };
+    let batch = toll_files.len() > 1;
+    if let TollOutput::Path(output_dir) = &output {
+        if batch && !dry_run {
+            if let Err(error) = create_dir_all(output_dir) {
+                eprintln!("Unable to create directory {} : {}", output_dir.display(), error);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    let mut tolls = Vec::new();
+    let mut failed = false;
+    for toll_file in &toll_files {
+        let output_file = output.output_file(toll_file, batch);
+        let output_file = if dry_run { None } else { Some(output_file.as_path()) };
+        match price_service.build_matrix(toll_file, output_file, &options) {
+            Ok(toll_audits) => tolls.extend(toll_audits),
+            Err(error) => {
+                eprintln!("{}", error);
+                failed = true;
+            }
+        }
+    }
+    if dry_run || min_coverage.is_some() {
+        print_coverage(&tolls);
+    }
+    if let Some(min_coverage) = min_coverage {
+        for toll in tolls.iter().filter(|toll| !toll.skipped && toll.coverage().priced_percent() < min_coverage) {
+            eprintln!("Toll {} has {:.1}% of its prices, below {}%", toll.toll_id, toll.coverage().priced_percent(), min_coverage);
+            failed = true;
+        }
+    }
+    let exit_code = write_report(args, &price_service, &tolls);
+    if failed || tolls.iter().any(|toll| toll.failed) {
+        return ExitCode::FAILURE;
+    }
+    exit_code
+}
+
+fn print_coverage(tolls: &[TollAudit]) {
+    println!("{:<30} {:>8} {:>8} {:>8} {:>9} {:>8}", "Toll", "Sections", "Pairs", "Priced", "Obsolete", "Missing");
+    for toll in tolls {
+        if toll.skipped {
+            println!("{:<30} {:>8} skipped", toll.toll_id, toll.sections);
+            continue;
+        }
+        let coverage = toll.coverage();
+        println!("{:<30} {:>8} {:>8} {:>7.1}% {:>8.1}% {:>7.1}%",
+                 toll.toll_id,
+                 toll.sections,
+                 coverage.pairs,
+                 coverage.priced_percent(),
+                 coverage.obsolete_percent(),
+                 coverage.missing_percent());
+        if !toll.skipped_categories.is_empty() {
+            let categories = toll.skipped_categories.iter().map(Category::to_string).collect::<Vec<_>>();
+            println!("{:<30} no price for {}", "", categories.join(", "));
+        }
+    }
 }
 
-fn command_get_prices(args: &Vec<String>) -> ExitCode {
+fn command_diff_matrix(args: &[String]) -> ExitCode {
+    if args.len() < 3 {
+        return usage();
+    }
+    let toll_file = Path::new(&args[2]);
+    let options = match matrix_options(args) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}", error);
+            return usage();
+        }
+    };
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    let tolls = match price_service.diff_matrix(toll_file, &options) {
+        Ok(tolls) => tolls,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+    for toll in &tolls {
+        println!("{} : {} changed prices", toll.toll_id, toll.changes.len());
+        for error in &toll.errors {
+            println!("\t{}", error);
+        }
+        for mismatch in &toll.currency_mismatches {
+            println!("\tNo exchange rate from {} for {} of {}", mismatch.currency, mismatch.key, mismatch.file);
+        }
+        for change in &toll.changes {
+            println!("\t{}", change);
+        }
+    }
+    write_json(args, &tolls)
+}
+
+fn command_get_prices(args: &[String]) -> ExitCode {
     if args.len() < 3 {
         return usage();
     }
     let entry_name = &args[2];
-    let price_service = PriceService::new();
-    price_service.get_prices(entry_name);
+    let date = match date_option(args) {
+        Ok(date) => date,
+        Err(error) => {
+            eprintln!("{}", error);
+            return usage();
+        }
+    };
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    println!("Getting prices for {} on {}", entry_name, date);
+    let prices = price_service.get_prices(entry_name, date);
+    if prices.is_empty() {
+        println!("No prices found for {}", entry_name);
+    }
+    for (key, price) in prices {
+        println!("{} {}", key.exit, price.price);
+    }
     ExitCode::SUCCESS
 }
 
-fn command_get_station(args: &Vec<String>) -> ExitCode {
+fn command_get_station(args: &[String]) -> ExitCode {
     if args.len() < 3 {
         return usage();
     }
     let station_name = &args[2];
-    let price_service = PriceService::new();
-    price_service.get_station(station_name);
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    println!("Getting station for {}", station_name);
+    let stations = price_service.find_stations(station_name);
+    if stations.is_empty() {
+        println!("No station found for {}", price_service.name_normalizer().normalize(station_name));
+    }
+    for station in stations {
+        println!("{}", station);
+    }
     ExitCode::SUCCESS
 }
 
-fn command_check_prices() -> ExitCode {
-    let price_service = PriceService::new();
+fn command_get_plaza(args: &[String]) -> ExitCode {
+    if args.len() < 3 {
+        return usage();
+    }
+    let plaza_name = &args[2];
+    let date = match date_option(args) {
+        Ok(date) => date,
+        Err(error) => {
+            eprintln!("{}", error);
+            return usage();
+        }
+    };
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    println!("Getting plaza prices for {} on {}", plaza_name, date);
+    let mut found_prices = false;
+    for category in all::<Category>() {
+        let plaza = price_service.name_normalizer().normalize(plaza_name);
+        if let Some(price) = price_service.get_plaza_price(&plaza, category, option_value(args, "--network").map(String::as_str), date) {
+            found_prices = true;
+            println!("{:<15} {}", category, price);
+        }
+    }
+    if !found_prices {
+        println!("No plaza prices found for {}", price_service.name_normalizer().normalize(plaza_name));
+    }
+    ExitCode::SUCCESS
+}
+
+fn command_get_history(args: &[String]) -> ExitCode {
+    if args.len() < 4 {
+        return usage();
+    }
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    let entry = price_service.name_normalizer().normalize(&args[2]);
+    let exit = price_service.name_normalizer().normalize(&args[3]);
+    println!("Getting price history for {} -> {}", entry, exit);
+    let mut found_prices = false;
+    for category in all::<Category>() {
+        let key = PriceKey {
+            entry: entry.to_string(),
+            exit: exit.to_string(),
+            category,
+            network: option_value(args, "--network").cloned()
+        };
+        for price in price_service.get_price_history(&key) {
+            found_prices = true;
+            println!("{} from {} : {}", category, price.effective_from(), price);
+        }
+    }
+    if !found_prices {
+        println!("No prices found for {} -> {}", entry, exit);
+    }
+    ExitCode::SUCCESS
+}
+
+/// Alias a section name to a station, the best suggestion by default.
+fn command_add_alias(args: &[String]) -> ExitCode {
+    if args.len() < 3 {
+        return usage();
+    }
+    let section_name = &args[2];
+    let paths = price_paths(args);
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    let station = match args.get(3).filter(|arg| !arg.starts_with("--")) {
+        Some(station) => price_service.name_normalizer().normalize(station),
+        None => {
+            let suggestions = price_service.suggest_stations(section_name, 5);
+            for suggestion in &suggestions {
+                println!("{:.2} {}", suggestion.score, suggestion.name);
+            }
+            match suggestions.into_iter().next() {
+                Some(suggestion) => suggestion.name,
+                None => {
+                    eprintln!("No station close to {}", section_name);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    };
+    if !price_service.station_names().contains(&station) {
+        eprintln!("Unknown station {}", station);
+        return ExitCode::FAILURE;
+    }
+    match NameNormalizer::add_alias(&paths.alias_file, section_name, &station) {
+        Ok(line) => {
+            println!("Added {} to {}", line, paths.alias_file.display());
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("Unable to add alias to {} : {}", paths.alias_file.display(), error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn command_lint_aliases(args: &[String]) -> ExitCode {
+    let paths = price_paths(args);
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    let name_normalizer = price_service.name_normalizer();
+    let issues = lint_aliases(name_normalizer.aliases(), price_service.station_names());
+    for error in name_normalizer.errors() {
+        println!("{}", error);
+    }
+    for issue in &issues {
+        println!("{}:{}", paths.alias_file.display(), issue);
+    }
+    println!("{} aliases, {} invalid lines, {} issues", name_normalizer.aliases().len(), name_normalizer.errors().len(), issues.len());
+    if issues.is_empty() && name_normalizer.errors().is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn command_check_prices(args: &[String]) -> ExitCode {
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
     println!("Price service loaded : {}", price_service);
+    if args.iter().any(|arg| arg == "--conflicts") {
+        let conflicts = &price_service.load_audit().conflicts;
+        println!("{} conflicting prices", conflicts.len());
+        for conflict in conflicts {
+            println!("\t{}", conflict);
+        }
+        let symmetry_conflicts = &price_service.load_audit().symmetry_conflicts;
+        println!("{} matrix prices contradicting a symmetric triangle", symmetry_conflicts.len());
+        for conflict in symmetry_conflicts {
+            println!("\t{}", conflict);
+        }
+    }
+    write_report(args, &price_service, &[])
+}
+
+fn command_check_consistency(args: &[String]) -> ExitCode {
+    let mut options = match date_option(args) {
+        Ok(date) => ConsistencyOptions::new(date),
+        Err(error) => {
+            eprintln!("{}", error);
+            return usage();
+        }
+    };
+    let thresholds = [
+        ("--detour", &mut options.detour_ratio),
+        ("--outlier", &mut options.outlier_ratio),
+        ("--max-change", &mut options.max_change)
+    ];
+    for (option, threshold) in thresholds {
+        match number_option(args, option) {
+            Ok(Some(value)) => *threshold = value,
+            Ok(None) => {}
+            Err(error) => {
+                eprintln!("{}", error);
+                return usage();
+            }
+        }
+    }
+    // the section locations of the tolls, for the prices per km
+    let mut toll_file_list: Vec<TollFile> = Vec::new();
+    if let Some(input) = args.get(2).filter(|arg| !arg.starts_with("--")) {
+        let paths = match toll_files(input) {
+            Ok(paths) => paths,
+            Err(error) => {
+                eprintln!("{}", error);
+                return ExitCode::FAILURE;
+            }
+        };
+        for path in paths {
+            match load_toll_file(&path) {
+                Ok(toll_file) => toll_file_list.push(toll_file),
+                Err(error) => {
+                    eprintln!("Failed to load toll file {}\n{}", path.display(), error);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+    }
+    let tolls = toll_file_list.iter().flat_map(|toll_file| &toll_file.tolls).collect::<Vec<_>>();
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    let issues = price_service.check_consistency(&tolls, &options);
+    println!("{} suspicious prices on {}", issues.len(), options.date);
+    for issue in &issues {
+        println!("\t{}", issue);
+    }
+    write_json(args, &issues)
+}
+
+fn command_price_evolution(args: &[String]) -> ExitCode {
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    let operator = option_value(args, "--operator");
+    let evolutions = price_service.price_evolution()
+        .into_iter()
+        .filter(|evolution| operator.is_none_or(|operator| evolution.operator.eq_ignore_ascii_case(operator)))
+        .collect::<Vec<_>>();
+    for evolution in &evolutions {
+        println!("{}", evolution);
+        for key in &evolution.vanished {
+            println!("\tvanished {}", key);
+        }
+        for key in &evolution.appeared {
+            println!("\tappeared {}", key);
+        }
+    }
+    write_json(args, &evolutions)
+}
+
+/// Default address of the serve command.
+const SERVE_ADDRESS: &str = "127.0.0.1:8080";
+
+fn command_serve(args: &[String]) -> ExitCode {
+    let address = option_value(args, "--address").map(String::as_str).unwrap_or(SERVE_ADDRESS);
+    match PriceServer::new(price_paths(args), address) {
+        Ok(mut server) => {
+            server.run();
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn command_interactive(args: &[String]) -> ExitCode {
+    let date = match date_option(args) {
+        Ok(date) => date,
+        Err(error) => {
+            eprintln!("{}", error);
+            return usage();
+        }
+    };
+    let price_service = match load_price_service(args) {
+        Ok(price_service) => price_service,
+        Err(exit_code) => return exit_code
+    };
+    if let Err(error) = PriceRepl::new(price_service, date).run() {
+        eprintln!("{}", error);
+        return ExitCode::FAILURE;
+    }
     ExitCode::SUCCESS
 }
 
@@ -66,12 +572,30 @@ fn main() -> ExitCode {
     let first_arg = &args[1];
     if first_arg == "build-matrix" {
         return command_build_matrix(&args);
+    } else if first_arg == "diff-matrix" {
+        return command_diff_matrix(&args);
+    } else if first_arg == "add-alias" {
+        return command_add_alias(&args);
+    } else if first_arg == "lint-aliases" {
+        return command_lint_aliases(&args);
     } else if first_arg == "check-prices" {
-        return command_check_prices();
+        return command_check_prices(&args);
+    } else if first_arg == "check-consistency" {
+        return command_check_consistency(&args);
+    } else if first_arg == "price-evolution" {
+        return command_price_evolution(&args);
+    } else if first_arg == "serve" {
+        return command_serve(&args);
+    } else if first_arg == "interactive" {
+        return command_interactive(&args);
     } else if first_arg == "get-station" {
         return command_get_station(&args);
     } else if first_arg == "get-prices" {
         return command_get_prices(&args);
+    } else if first_arg == "get-plaza" {
+        return command_get_plaza(&args);
+    } else if first_arg == "get-history" {
+        return command_get_history(&args);
     }
     usage()
 }
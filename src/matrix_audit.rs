@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use chrono::NaiveDate;
+use serde::Serialize;
+use crate::category::Category;
+use crate::money::Currency;
+use crate::name_normalizer::Suggestion;
+use crate::price::Price;
+use crate::price_grid::PriceKey;
+
+/// What was found while building the matrix of a category.
+#[derive(Serialize, Default)]
+pub struct MatrixAudit {
+    pub found: u32,
+    pub obsolete: u32,
+    pub not_found: u32,
+    /// Unknown prices for which the value of the input matrix was kept
+    pub kept: u32,
+    /// Unknown prices without a value of the input matrix to keep, leaving the toll unchanged
+    pub not_kept: u32,
+    pub obsolete_files: Vec<String>,
+    pub missing: Vec<PriceKey>,
+    pub derived: Vec<DerivedPrice>,
+    /// Prices converted from another currency than the one of the toll
+    pub converted: u32,
+    /// Prices refused because they cannot be converted to the currency of the toll
+    pub currency_mismatches: Vec<CurrencyMismatch>
+}
+
+/// A price of another currency than the one of the toll, without exchange rate.
+#[derive(Serialize)]
+pub struct CurrencyMismatch {
+    pub key: PriceKey,
+    pub currency: Currency,
+    pub file: String
+}
+
+/// A missing price filled with the sum of the prices to and from another section, to be reviewed.
+#[derive(Serialize)]
+pub struct DerivedPrice {
+    pub key: PriceKey,
+    pub via: String,
+    pub price: f64
+}
+
+impl MatrixAudit {
+    /// Count a found price, logging it when it is obsolete on the given date.
+    pub(crate) fn add_found(&mut self, key: &PriceKey, price: &Price, date: NaiveDate) {
+        if price.is_obsolete(date) {
+            match price.metadata.effective_to {
+                Some(effective_to) => println!("Price is obsolete (valid until {}) for {}", effective_to, key),
+                None => println!("Price is obsolete (from {}) for {}", price.year, key)
+            }
+            self.obsolete += 1;
+            self.add_obsolete_file(&price.file);
+        }
+        self.found += 1;
+    }
+
+    pub(crate) fn add_obsolete_file(&mut self, file: &str) {
+        if !self.obsolete_files.iter().any(|obsolete_file| obsolete_file == file) {
+            self.obsolete_files.push(file.to_string());
+        }
+    }
+}
+
+/// The matrix audits of a toll.
+#[derive(Serialize)]
+pub struct TollAudit {
+    pub toll_id: String,
+    pub sections: usize,
+    pub skipped: bool,
+    /// Left unchanged because of unknown prices
+    pub failed: bool,
+    pub categories: BTreeMap<Category, MatrixAudit>,
+    /// Categories without any known price whose matrix is not written
+    pub skipped_categories: Vec<Category>,
+    /// The plaza prices of the fixed price rule
+    pub fixed_prices: BTreeMap<Category, MatrixAudit>,
+    pub unknown_sections: Vec<UnknownSection>
+}
+
+/// A section whose name is not found in the price grids, with the closest known names.
+#[derive(Serialize)]
+pub struct UnknownSection {
+    pub section_id: String,
+    pub normalized: String,
+    pub suggestions: Vec<Suggestion>
+}
+
+impl TollAudit {
+    pub(crate) fn new(toll_id: &str, sections: usize) -> TollAudit {
+        TollAudit {
+            toll_id: toll_id.to_string(),
+            sections,
+            skipped: false,
+            failed: false,
+            categories: BTreeMap::new(),
+            skipped_categories: Vec::new(),
+            fixed_prices: BTreeMap::new(),
+            unknown_sections: Vec::new()
+        }
+    }
+}
+
+impl TollAudit {
+    /// The coverage of the entry/exit matrices and of the fixed prices built, a fixed price counting as one pair.
+    /// The categories without any price are skipped.
+    pub fn coverage(&self) -> Coverage {
+        let mut coverage = Coverage::default();
+        for audit in self.categories.values().chain(self.fixed_prices.values()).filter(|audit| audit.found > 0) {
+            coverage.pairs += audit.found + audit.derived.len() as u32 + audit.not_found;
+            coverage.priced += audit.found + audit.derived.len() as u32;
+            coverage.obsolete += audit.obsolete;
+            coverage.missing += audit.not_found;
+        }
+        coverage
+    }
+}
+
+/// Count of the entry/exit pairs of the matrices and of the fixed prices of a toll.
+#[derive(Default, Debug, PartialEq)]
+pub struct Coverage {
+    pub pairs: u32,
+    /// Found or derived
+    pub priced: u32,
+    pub obsolete: u32,
+    pub missing: u32
+}
+
+impl Coverage {
+    /// Percent of priced pairs, 0 without any pair.
+    pub fn priced_percent(&self) -> f64 {
+        self.percent(self.priced)
+    }
+
+    pub fn obsolete_percent(&self) -> f64 {
+        self.percent(self.obsolete)
+    }
+
+    pub fn missing_percent(&self) -> f64 {
+        self.percent(self.missing)
+    }
+
+    fn percent(&self, count: u32) -> f64 {
+        if self.pairs == 0 {
+            return 0.0;
+        }
+        count as f64 * 100.0 / self.pairs as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::category::Category;
+    use super::{MatrixAudit, TollAudit};
+
+    #[test]
+    fn coverage() {
+        let mut toll_audit = TollAudit::new("A89", 3);
+        toll_audit.categories.insert(Category::Car, MatrixAudit {
+            found: 4,
+            obsolete: 1,
+            not_found: 2,
+            ..MatrixAudit::default()
+        });
+        toll_audit.categories.insert(Category::Motorcycle, MatrixAudit {
+            not_found: 6,
+            ..MatrixAudit::default()
+        });
+        let coverage = toll_audit.coverage();
+        assert_eq!(6, coverage.pairs);
+        assert_eq!(4, coverage.priced);
+        assert!((coverage.priced_percent() - 66.67).abs() < 0.01);
+
+        // a toll priced only by its plaza
+        let mut fixed_price_audit = TollAudit::new("A40", 1);
+        fixed_price_audit.fixed_prices.insert(Category::Car, MatrixAudit {
+            found: 1,
+            ..MatrixAudit::default()
+        });
+        fixed_price_audit.fixed_prices.insert(Category::Motorcycle, MatrixAudit {
+            not_found: 1,
+            ..MatrixAudit::default()
+        });
+        let coverage = fixed_price_audit.coverage();
+        assert_eq!(1, coverage.pairs);
+        assert_eq!(100.0, coverage.priced_percent());
+    }
+}
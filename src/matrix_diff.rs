@@ -0,0 +1,89 @@
+use std::fmt;
+use serde::Serialize;
+use crate::matrix_audit::CurrencyMismatch;
+use crate::price_grid::PriceKey;
+
+/// Smallest price change reported, below a cent the prices are the same.
+const PRICE_EPSILON: f64 = 0.005;
+
+/// A cell of a matrix whose price would change.
+#[derive(Serialize)]
+pub struct CellDiff {
+    pub key: PriceKey,
+    /// None when the toll has no matrix for the category
+    pub old_price: Option<f64>,
+    pub new_price: f64,
+    pub delta: f64,
+    /// None when there is no old price to compare with
+    pub percentage: Option<f64>,
+    /// The price file of the new price, None for an unknown price
+    pub file: Option<String>
+}
+
+impl CellDiff {
+    pub(crate) fn new(key: PriceKey, old_price: Option<f64>, new_price: f64, file: Option<String>) -> Option<CellDiff> {
+        let delta = ((new_price - old_price.unwrap_or(0.0)) * 100.0).round() / 100.0;
+        if old_price.is_some() && delta.abs() < PRICE_EPSILON {
+            return None;
+        }
+        let percentage = old_price
+            .filter(|old_price| *old_price != 0.0)
+            .map(|old_price| delta / old_price * 100.0);
+        Some(CellDiff {
+            key,
+            old_price,
+            new_price,
+            delta,
+            percentage,
+            file
+        })
+    }
+}
+
+impl fmt::Display for CellDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.old_price {
+            Some(old_price) => write!(f, "{} : {:.2} -> {:.2} ({:+.2}", self.key, old_price, self.new_price, self.delta)?,
+            None => write!(f, "{} : none -> {:.2} ({:+.2}", self.key, self.new_price, self.delta)?
+        }
+        if let Some(percentage) = self.percentage {
+            write!(f, ", {:+.1}%", percentage)?;
+        }
+        write!(f, ") {}", self.file.as_deref().unwrap_or("unknown price"))
+    }
+}
+
+/// The cells of the matrices of a toll whose price would change.
+#[derive(Serialize)]
+pub struct TollDiff {
+    pub toll_id: String,
+    pub changes: Vec<CellDiff>,
+    /// Why the toll would be left unchanged by build-matrix
+    pub errors: Vec<String>,
+    /// The prices without exchange rate to the currency of the toll
+    pub currency_mismatches: Vec<CurrencyMismatch>
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::category::Category;
+    use crate::price_grid::PriceKey;
+    use super::CellDiff;
+
+    fn key() -> PriceKey {
+        PriceKey {
+            entry: "A".to_string(),
+            exit: "B".to_string(),
+            category: Category::Car,
+            network: None
+        }
+    }
+
+    #[test]
+    fn cell_diff() {
+        assert!(CellDiff::new(key(), Some(1.5), 1.501, None).is_none());
+        let diff = CellDiff::new(key(), Some(2.0), 2.5, Some("2024_APRR.tsv".to_string())).unwrap();
+        assert_eq!(Some(25.0), diff.percentage);
+        assert_eq!("Car A->B : 2.00 -> 2.50 (+0.50, +25.0%) 2024_APRR.tsv", diff.to_string());
+    }
+}
@@ -0,0 +1,163 @@
+use std::cmp::Ordering;
+use std::fmt;
+use serde::{Serialize, Serializer};
+
+/// Number of minor units in a major unit, prices being given to the hundredth.
+const MINOR_UNITS: i64 = 100;
+
+/// An ISO 4217 currency code, such as EUR.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct Currency([u8; 3]);
+
+impl Currency {
+    pub const EUR: Currency = Currency(*b"EUR");
+
+    /// Parse a code of three letters, whatever their case.
+    pub fn new(code: &str) -> Option<Currency> {
+        let code: [u8; 3] = code.trim().to_ascii_uppercase().into_bytes().try_into().ok()?;
+        code.iter().all(u8::is_ascii_uppercase).then_some(Currency(code))
+    }
+
+    pub fn code(&self) -> &str {
+        // only made of ascii letters
+        std::str::from_utf8(&self.0).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.code())
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_str(self.code())
+    }
+}
+
+/// An exact amount of money, counted in hundredths of its currency.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Money {
+    minor: i64,
+    currency: Currency
+}
+
+impl Money {
+    pub fn new(minor: i64, currency: Currency) -> Money {
+        Money {
+            minor,
+            currency
+        }
+    }
+
+    /// Parse a decimal amount such as 4.35, 4,35 or 1 234,5, rounding the third decimal half away from zero.
+    pub fn parse(text: &str, currency: Currency) -> Result<Money, String> {
+        let invalid = || format!("Invalid amount \"{}\"", text);
+        let digits = text.trim().replace([' ', '\u{a0}'], "");
+        let (negative, digits) = match digits.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, digits.strip_prefix('+').unwrap_or(&digits))
+        };
+        let (integer, fraction) = digits.split_once(['.', ',']).unwrap_or((digits, ""));
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if integer.is_empty() && fraction.is_empty() || !all_digits(integer) || !all_digits(fraction) {
+            return Err(invalid());
+        }
+        let mut minor: i64 = 0;
+        for digit in integer.chars().chain(fraction.chars().chain("00".chars()).take(2)) {
+            minor = minor.checked_mul(10)
+                .and_then(|minor| minor.checked_add(digit as i64 - '0' as i64))
+                .ok_or_else(|| format!("Amount \"{}\" too large", text))?;
+        }
+        if fraction.chars().nth(2).is_some_and(|digit| digit >= '5') {
+            minor = minor.checked_add(1).ok_or_else(|| format!("Amount \"{}\" too large", text))?;
+        }
+        Ok(Money::new(if negative { -minor } else { minor }, currency))
+    }
+
+    /// The amount of a decimal value, rounded to the closest hundredth, half away from zero.
+    pub fn from_f64(value: f64, currency: Currency) -> Money {
+        Money::new((value * MINOR_UNITS as f64).round() as i64, currency)
+    }
+
+    /// The amount in hundredths.
+    pub fn minor(&self) -> i64 {
+        self.minor
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// The amount as written in the matrices of the toll files.
+    pub fn to_f64(&self) -> f64 {
+        self.minor as f64 / MINOR_UNITS as f64
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.minor == 0
+    }
+
+    /// The sum of two amounts of the same currency.
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        if self.currency != other.currency {
+            return None;
+        }
+        self.minor.checked_add(other.minor).map(|minor| Money::new(minor, self.currency))
+    }
+}
+
+impl PartialOrd for Money {
+    /// Amounts of different currencies are not comparable.
+    fn partial_cmp(&self, other: &Money) -> Option<Ordering> {
+        (self.currency == other.currency).then(|| self.minor.cmp(&other.minor))
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.minor < 0 { "-" } else { "" };
+        let minor = self.minor.unsigned_abs();
+        let units = MINOR_UNITS as u64;
+        write!(f, "{}{}.{:02} {}", sign, minor / units, minor % units, self.currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Currency, Money};
+
+    fn parse(text: &str) -> Result<i64, String> {
+        Money::parse(text, Currency::EUR).map(|money| money.minor())
+    }
+
+    #[test]
+    fn parse_exact_decimals() {
+        assert_eq!(Ok(435), parse("4.35"));
+        assert_eq!(Ok(435), parse("4,35"));
+        assert_eq!(Ok(46261), parse("462.61"));
+        assert_eq!(Ok(123450), parse("1 234,5"));
+        assert_eq!(Ok(700), parse("7"));
+        assert_eq!(Ok(436), parse("4.355"));
+        assert_eq!(Ok(434), parse("4.3449"));
+        assert_eq!(Ok(-150), parse("-1.5"));
+        assert_eq!(Ok(-1), parse("-0.005"));
+        assert_eq!(Ok(-436), parse("-4.355"));
+        assert!(parse("4.3.5").is_err());
+        assert!(parse("").is_err());
+        assert!(parse("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn convert_and_display() {
+        assert_eq!(435, Money::from_f64(4.35, Currency::EUR).minor());
+        assert_eq!("462.61 EUR", Money::new(46261, Currency::EUR).to_string());
+        assert_eq!("-0.05 EUR", Money::new(-5, Currency::EUR).to_string());
+        assert_eq!(Some(Currency::EUR), Currency::new("eur"));
+        assert_eq!(None, Currency::new("€"));
+        let chf = Money::new(100, Currency::new("CHF").unwrap());
+        assert_eq!(None, Money::new(100, Currency::EUR).checked_add(chf));
+        assert_eq!(None, Money::new(100, Currency::EUR).partial_cmp(&chf));
+    }
+}
@@ -1,58 +1,349 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs::{read_to_string, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use serde::Serialize;
 use unidecode::unidecode;
-use crate::io_tools::read_lines;
+use crate::price_grid::PriceLoadError;
 
+/// Normalize the station names of the price grids and of the toll files so that they can be matched.
+pub struct NameNormalizer {
+    map: HashMap<String, String>,
+    /// The aliases of a price file, a motorway or an operator, by uppercase scope
+    scoped_maps: HashMap<String, HashMap<String, String>>,
+    aliases: Vec<Alias>,
+    errors: Vec<PriceLoadError>
+}
+
+/// A line of the alias file, lines starting at 1.
+#[derive(Clone, Debug)]
+pub struct Alias {
+    pub source: String,
+    pub target: String,
+    /// The price file, motorway or operator the alias is limited to
+    pub scope: Option<String>,
+    pub line: usize
+}
+
+/// Where a name is read, to apply the aliases of its price files, motorways or operators before the global ones.
+#[derive(Clone, Debug, Default)]
+pub struct NameScope {
+    pub files: Vec<String>,
+    pub motorways: Vec<String>,
+    pub operators: Vec<String>
+}
+
+impl NameScope {
+    /// The scope of a price file, its motorways being read from its name, 2023_ASF-A89-A10.tsv -> A89, A10, and from its network.
+    pub fn for_file(file: &str, operator: Option<&str>, network: Option<&str>) -> NameScope {
+        let mut scope = NameScope {
+            files: vec![file.to_string()],
+            motorways: motorways(file),
+            operators: operator.iter().map(|operator| operator.to_string()).collect()
+        };
+        scope.add_motorways(network.as_slice());
+        scope
+    }
+
+    pub fn for_motorways(motorways: &[&str]) -> NameScope {
+        let mut scope = NameScope::default();
+        scope.add_motorways(motorways);
+        scope
+    }
+
+    /// Add the file and operator of the scope of a price file sharing a motorway with this scope,
+    /// so that the aliases of the price grids of a motorway also apply to the sections of its tolls.
+    pub fn add_price_file(&mut self, file_scope: &NameScope) {
+        if !file_scope.motorways.iter().any(|motorway| self.motorways.contains(motorway)) {
+            return;
+        }
+        for file in &file_scope.files {
+            if !self.files.contains(file) {
+                self.files.push(file.to_string());
+            }
+        }
+        for operator in &file_scope.operators {
+            if !self.operators.iter().any(|known| known.eq_ignore_ascii_case(operator)) {
+                self.operators.push(operator.to_string());
+            }
+        }
+    }
+
+    fn add_motorways(&mut self, motorways: &[&str]) {
+        for motorway in motorways.iter().map(|motorway| motorway.trim().to_uppercase()) {
+            if !motorway.is_empty() && !self.motorways.contains(&motorway) {
+                self.motorways.push(motorway);
+            }
+        }
+    }
 
-pub(crate) struct NameNormalizer {
-    map: HashMap<String, String>
+    /// The scopes of the aliases to apply, the most specific first.
+    fn keys(&self) -> Vec<String> {
+        self.files.iter()
+            .chain(self.motorways.iter())
+            .chain(self.operators.iter())
+            .map(|key| key.to_uppercase())
+            .collect()
+    }
+}
+
+/// The motorway numbers found in a name.
+fn motorways(name: &str) -> Vec<String> {
+    let mut motorways = Vec::new();
+    for token in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let is_motorway = token.len() > 1
+            && token.starts_with(['A', 'a'])
+            && token[1..].chars().all(|c| c.is_ascii_digit());
+        if is_motorway && !motorways.contains(&token.to_uppercase()) {
+            motorways.push(token.to_uppercase());
+        }
+    }
+    motorways
 }
 
 impl NameNormalizer {
-    pub(crate) fn new() -> NameNormalizer {
-        const ALIAS_FILENAME: &'static str = "prices/alias.csv";
+    /// Load the aliases of an alias file, each csv record being "normalized name,name used in the price grids",
+    /// optionally followed by the price file, motorway or operator the alias is limited to.
+    /// Names containing a comma are quoted. When a name has several aliases the last one is used.
+    /// Invalid lines are skipped and kept in [`NameNormalizer::errors`], an unreadable file is an error.
+    pub fn new(alias_file: &Path) -> Result<NameNormalizer, PriceLoadError> {
+        let file = alias_file.display().to_string();
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_path(alias_file)
+            .map_err(|error| PriceLoadError::Io {
+                file: file.to_string(),
+                cause: format!("cannot read alias file, {}", error)
+            })?;
         let mut map: HashMap<String, String> = HashMap::new();
-        if let Ok(lines) = read_lines(ALIAS_FILENAME) {
-            for line in lines {
-                let line = line.unwrap();
-                let tokens = line.split(",");
-                let tokens = tokens.map(|token| token.to_string()).collect::<Vec<String>>();
-                if tokens.len() != 2 {
-                    println!("Invalid alias line {}", line);
+        let mut scoped_maps: HashMap<String, HashMap<String, String>> = HashMap::new();
+        let mut aliases = Vec::new();
+        let mut errors = Vec::new();
+        for record in reader.records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(error) => {
+                    let line = error.position().map(|position| position.line() as usize).unwrap_or_default();
+                    if line == 0 {
+                        return Err(PriceLoadError::Io {
+                            file: file.to_string(),
+                            cause: format!("cannot read alias file, {}", error)
+                        });
+                    }
+                    errors.push(PriceLoadError::InvalidLine {
+                        file: file.to_string(),
+                        line,
+                        cause: format!("invalid alias, {}", error)
+                    });
                     continue;
                 }
-                let first_token = tokens.get(0).unwrap().to_string();
-                let second_token = tokens.get(1).unwrap().to_string();
-                map.insert(first_token, second_token);
+            };
+            let line = record.position().map(|position| position.line() as usize).unwrap_or_default();
+            if record.len() != 2 && record.len() != 3 {
+                let record = record.iter().collect::<Vec<&str>>().join(",");
+                println!("Invalid alias line {}", record);
+                errors.push(PriceLoadError::InvalidLine {
+                    file: file.to_string(),
+                    line,
+                    cause: format!("invalid alias {}", record)
+                });
+                continue;
             }
-        } else {
-            panic!("Unable to proceed : cannot read alias file {}", ALIAS_FILENAME);
-        }
-        NameNormalizer {
-            map
+            let alias = Alias {
+                source: record[0].to_string(),
+                target: record[1].to_string(),
+                scope: record.get(2).map(str::trim).filter(|scope| !scope.is_empty()).map(str::to_string),
+                line
+            };
+            match &alias.scope {
+                Some(scope) => scoped_maps.entry(scope.to_uppercase()).or_default(),
+                None => &mut map
+            }.insert(alias.source.to_string(), alias.target.to_string());
+            aliases.push(alias);
         }
+        Ok(NameNormalizer {
+            map,
+            scoped_maps,
+            aliases,
+            errors
+        })
+    }
+
+    /// Every line of the alias file, in order.
+    pub fn aliases(&self) -> &[Alias] {
+        &self.aliases
+    }
+
+    /// The invalid lines of the alias file.
+    pub fn errors(&self) -> &[PriceLoadError] {
+        &self.errors
+    }
+
+    /// Uppercase the name without accents nor separators, then apply the global aliases.
+    pub fn normalize(&self, name: &str) -> String {
+        self.normalize_in(name, &NameScope::default())
     }
 
-    pub(crate) fn normalize(&self, name: &str) -> String {
-        let normalized = unidecode(name)
-            .to_uppercase()
-            .replace(" - ", " ")
-            .replace(" / ", " ")
-            .replace('-', " ")
-            .replace('/', " ")
-            .replace('\'', " ");
-        if let Some(normalized_name) = self.map.get(&normalized) {
+    /// Uppercase the name without accents nor separators, then apply the aliases of the scope or else the global ones.
+    pub fn normalize_in(&self, name: &str, scope: &NameScope) -> String {
+        let normalized = clean_name(name);
+        let scoped_name = scope.keys().iter()
+            .filter_map(|key| self.scoped_maps.get(key))
+            .find_map(|scoped_map| scoped_map.get(&normalized));
+        if let Some(normalized_name) = scoped_name.or_else(|| self.map.get(&normalized)) {
             normalized_name.to_string()
         } else {
             normalized
         }
     }
+
+    /// The known names closest to the given name, best first, keeping those sharing at least half of their letters or words.
+    pub fn suggest<'b>(&self, name: &str, known_names: impl IntoIterator<Item = &'b String>, limit: usize) -> Vec<Suggestion> {
+        let name = self.normalize(name);
+        let mut suggestions = known_names.into_iter()
+            .map(|known_name| Suggestion {
+                name: known_name.to_string(),
+                score: similarity(&name, known_name)
+            })
+            .filter(|suggestion| suggestion.score >= MIN_SIMILARITY)
+            .collect::<Vec<Suggestion>>();
+        suggestions.sort_by(|first, second| second.score.total_cmp(&first.score).then_with(|| first.name.cmp(&second.name)));
+        suggestions.truncate(limit);
+        suggestions
+    }
+
+    /// Append "name,station" to the alias file, the name being cleaned as by [`NameNormalizer::normalize`].
+    /// Returns the added line.
+    pub fn add_alias(alias_file: &Path, name: &str, station: &str) -> io::Result<String> {
+        let content = read_to_string(alias_file)?;
+        let name = clean_name(name);
+        let name_normalizer = NameNormalizer::new(alias_file).map_err(io::Error::other)?;
+        if name_normalizer.map.contains_key(&name) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already has an alias", name)));
+        }
+        let line = format!("{},{}", csv_field(&name), csv_field(station));
+        let mut file = OpenOptions::new().append(true).open(alias_file)?;
+        if !content.is_empty() && !content.ends_with('\n') {
+            writeln!(file)?;
+        }
+        writeln!(file, "{}", line)?;
+        Ok(line)
+    }
+}
+
+/// Least similarity of a suggestion.
+const MIN_SIMILARITY: f64 = 0.5;
+
+/// A known name close to an unknown one, the score going from 0 to 1.
+#[derive(Clone, Debug, Serialize)]
+pub struct Suggestion {
+    pub name: String,
+    pub score: f64
+}
+
+/// Quote a csv field when needed.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Uppercase the name without accents nor separators.
+pub(crate) fn clean_name(name: &str) -> String {
+    unidecode(name)
+        .to_uppercase()
+        .replace(" - ", " ")
+        .replace(" / ", " ")
+        .replace(['-', '/', '\''], " ")
+}
+
+/// The best of the edit distance similarity and of the share of common words of two names.
+fn similarity(first: &str, second: &str) -> f64 {
+    let length = first.chars().count().max(second.chars().count());
+    if length == 0 {
+        return 1.0;
+    }
+    let edit_similarity = 1.0 - levenshtein(first, second) as f64 / length as f64;
+    let first_tokens = first.split_whitespace().collect::<HashSet<&str>>();
+    let second_tokens = second.split_whitespace().collect::<HashSet<&str>>();
+    let union = first_tokens.union(&second_tokens).count();
+    let token_similarity = if union == 0 { 0.0 } else { first_tokens.intersection(&second_tokens).count() as f64 / union as f64 };
+    edit_similarity.max(token_similarity)
+}
+
+fn levenshtein(first: &str, second: &str) -> usize {
+    let second = second.chars().collect::<Vec<char>>();
+    let mut previous_row = (0..=second.len()).collect::<Vec<usize>>();
+    for (first_index, first_char) in first.chars().enumerate() {
+        let mut row = vec![first_index + 1];
+        for (second_index, second_char) in second.iter().enumerate() {
+            let substitution = previous_row[second_index] + if first_char == *second_char { 0 } else { 1 };
+            row.push(substitution.min(previous_row[second_index + 1] + 1).min(row[second_index] + 1));
+        }
+        previous_row = row;
+    }
+    previous_row[second.len()]
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::price_paths::PricePaths;
+
     #[test]
     fn simple_normalize() {
-        let name_normalizer = super::NameNormalizer::new();
+        let name_normalizer = super::NameNormalizer::new(&PricePaths::default().alias_file).unwrap();
         assert_eq!("CHATEAU RENAULT", name_normalizer.normalize("CHATEAU-RENAULT"));
     }
+
+    #[test]
+    fn suggest_close_names() {
+        let name_normalizer = super::NameNormalizer::new(&PricePaths::default().alias_file).unwrap();
+        let known_names = ["TULLE NORD", "TULLE EST", "ALLAINES"].map(|name| name.to_string());
+        let suggestions = name_normalizer.suggest("Tulle-Nrod", &known_names, 5);
+        assert_eq!(vec!["TULLE NORD", "TULLE EST"], suggestions.iter().map(|suggestion| suggestion.name.as_str()).collect::<Vec<&str>>());
+        assert_eq!(4, super::levenshtein("TULLE NROD", "TULLE EST"));
+    }
+
+    #[test]
+    fn append_alias_after_last_line() {
+        let alias_file = std::env::temp_dir().join(format!("waze-toll-tool-alias-test-{}.csv", std::process::id()));
+        std::fs::write(&alias_file, "AMBERIEU,AMBERIEU EN BUGEY").unwrap();
+        let line = super::NameNormalizer::add_alias(&alias_file, "Tulle-Nrod", "TULLE NORD").unwrap();
+        assert!(super::NameNormalizer::add_alias(&alias_file, "TULLE NROD", "TULLE EST").is_err());
+        let content = std::fs::read_to_string(&alias_file).unwrap();
+        std::fs::remove_file(&alias_file).unwrap();
+        assert_eq!("TULLE NROD,TULLE NORD", line);
+        assert_eq!("AMBERIEU,AMBERIEU EN BUGEY\nTULLE NROD,TULLE NORD\n", content);
+    }
+
+    #[test]
+    fn quoted_aliases() {
+        let alias_file = std::env::temp_dir().join(format!("waze-toll-tool-quoted-alias-test-{}.csv", std::process::id()));
+        std::fs::write(&alias_file, "\"ST PIERRE, BARRIERE\",ST PIERRE\nINVALID\n").unwrap();
+        super::NameNormalizer::add_alias(&alias_file, "Sens, Nord", "SENS \"NORD\"").unwrap();
+        let name_normalizer = super::NameNormalizer::new(&alias_file).unwrap();
+        std::fs::remove_file(&alias_file).unwrap();
+        assert_eq!("ST PIERRE", name_normalizer.normalize("St Pierre, barrière"));
+        assert_eq!("SENS \"NORD\"", name_normalizer.normalize("SENS, NORD"));
+        assert_eq!(1, name_normalizer.errors().len());
+        assert_eq!(Some(2), name_normalizer.errors()[0].line());
+    }
+
+    #[test]
+    fn scoped_aliases() {
+        let alias_file = std::env::temp_dir().join(format!("waze-toll-tool-scoped-alias-test-{}.csv", std::process::id()));
+        std::fs::write(&alias_file, "SUD,ANGERS SUD,a11\nSUD,AIX SUD,AREA\nSUD,PARIS SUD\n").unwrap();
+        let name_normalizer = super::NameNormalizer::new(&alias_file).unwrap();
+        std::fs::remove_file(&alias_file).unwrap();
+        let file_scope = super::NameScope::for_file("2023_Cofiroute-A11-A28.tsv", Some("COFIROUTE"), None);
+        assert_eq!(vec!["A11", "A28"], file_scope.motorways);
+        assert_eq!("ANGERS SUD", name_normalizer.normalize_in("Sud", &file_scope));
+        assert_eq!("AIX SUD", name_normalizer.normalize_in("Sud", &super::NameScope::for_file("2023_AREA.tsv", Some("area"), None)));
+        assert_eq!("PARIS SUD", name_normalizer.normalize_in("Sud", &super::NameScope::for_motorways(&["A6"])));
+        assert_eq!("PARIS SUD", name_normalizer.normalize("Sud"));
+    }
 }
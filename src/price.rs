@@ -1,15 +1,44 @@
 use std::fmt;
 use std::fmt::Formatter;
+use std::sync::Arc;
+use chrono::{Datelike, NaiveDate};
+use crate::money::Money;
+use crate::price_grid::PriceMetadata;
 
+/// A price read from a price file.
 #[derive(Clone)]
-pub(crate) struct Price {
-    pub(crate) price: u16,
-    pub(crate) year: u16,
-    pub(crate) file: String
+pub struct Price {
+    /// Price in the currency of its file
+    pub price: Money,
+    pub year: u16,
+    pub file: String,
+    pub metadata: Arc<PriceMetadata>
+}
+
+impl Price {
+    /// First day of validity, the 1st of January of its year when not declared.
+    pub fn effective_from(&self) -> NaiveDate {
+        self.metadata.effective_from
+            .or(NaiveDate::from_ymd_opt(self.year as i32, 1, 1))
+            .unwrap_or(NaiveDate::MIN)
+    }
+
+    /// A price is obsolete after its declared validity end, or after its year when no end is declared.
+    pub fn is_obsolete(&self, date: NaiveDate) -> bool {
+        match self.metadata.effective_to {
+            Some(effective_to) => date > effective_to,
+            None => date.year() > self.year as i32
+        }
+    }
 }
 
 impl fmt::Display for Price {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{} {} {}", self.year, self.price, self.file)
     }
-}
\ No newline at end of file
+}
+
+/// The price of a history applying on the given date : the last one that took effect on or before it.
+pub fn price_on(history: &[Price], date: NaiveDate) -> Option<&Price> {
+    history.iter().rev().find(|price| price.effective_from() <= date)
+}
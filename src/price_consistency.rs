@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::fmt;
+use chrono::NaiveDate;
+use serde::Serialize;
+use crate::category::Category;
+use crate::money::{Currency, Money};
+use crate::price::{price_on, Price};
+use crate::price_grid::PriceKey;
+
+/// Mean radius of the earth, in km.
+const EARTH_RADIUS: f64 = 6371.0;
+/// Shortest distance between two sections giving a meaningful price per km.
+const MIN_DISTANCE: f64 = 1.0;
+
+/// Thresholds of the consistency checks.
+#[derive(Clone, Debug)]
+pub struct ConsistencyOptions {
+    /// Date on which the prices are compared
+    pub date: NaiveDate,
+    /// A trip costing more than this ratio of the sum of two trips through another station is reported
+    pub detour_ratio: f64,
+    /// A price per km more than this ratio above or below the median of its toll is reported
+    pub outlier_ratio: f64,
+    /// A price changing by more than this percentage from the previous year is reported
+    pub max_change: f64
+}
+
+impl ConsistencyOptions {
+    pub fn new(date: NaiveDate) -> ConsistencyOptions {
+        ConsistencyOptions {
+            date,
+            detour_ratio: 1.5,
+            outlier_ratio: 2.0,
+            max_change: 10.0
+        }
+    }
+}
+
+/// A price that is probably a typo.
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConsistencyIssue {
+    /// The reverse trip has another price
+    Asymmetric { key: PriceKey, price: f64, reverse_price: f64 },
+    /// The trip is much more expensive than going through another station
+    Detour { key: PriceKey, price: f64, via: String, via_price: f64 },
+    /// The price per km is far from the median of the toll
+    PerKmOutlier { toll_id: String, key: PriceKey, price: f64, distance: f64, price_per_km: f64, median_per_km: f64 },
+    /// The price changed too much from the previous year
+    YearlyChange { key: PriceKey, previous_price: f64, previous_file: String, price: f64, file: String, percentage: f64 }
+}
+
+impl fmt::Display for ConsistencyIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsistencyIssue::Asymmetric { key, price, reverse_price } =>
+                write!(f, "{} : {:.2} but {:.2} in the reverse direction", key, price, reverse_price),
+            ConsistencyIssue::Detour { key, price, via, via_price } =>
+                write!(f, "{} : {:.2} but {:.2} via {}", key, price, via_price, via),
+            ConsistencyIssue::PerKmOutlier { toll_id, key, price, distance, price_per_km, median_per_km } =>
+                write!(f, "{} {} : {:.2} for {:.1} km, {:.3} per km instead of {:.3}", toll_id, key, price, distance, price_per_km, median_per_km),
+            ConsistencyIssue::YearlyChange { key, previous_price, previous_file, price, file, percentage } =>
+                write!(f, "{} : {:.2} in {} -> {:.2} in {} ({:+.1}%)", key, previous_price, previous_file, price, file, percentage)
+        }
+    }
+}
+
+/// A priced trip between two sections of a toll.
+pub(crate) struct TollTrip {
+    pub(crate) key: PriceKey,
+    pub(crate) price: f64,
+    pub(crate) distance: f64
+}
+
+/// The trips whose reverse trip has another price on the date, reported once per pair of stations.
+pub(crate) fn asymmetric_prices(prices: &HashMap<PriceKey, Vec<Price>>, date: NaiveDate) -> Vec<ConsistencyIssue> {
+    let mut issues = Vec::new();
+    for (key, history) in prices.iter().filter(|(key, _)| key.entry < key.exit) {
+        let reverse_key = PriceKey {
+            entry: key.exit.to_string(),
+            exit: key.entry.to_string(),
+            ..key.clone()
+        };
+        let Some(price) = price_on(history, date) else {
+            continue;
+        };
+        let Some(reverse_price) = prices.get(&reverse_key).and_then(|history| price_on(history, date)) else {
+            continue;
+        };
+        if price.price != reverse_price.price {
+            issues.push(ConsistencyIssue::Asymmetric {
+                key: key.clone(),
+                price: euros(price),
+                reverse_price: euros(reverse_price)
+            });
+        }
+    }
+    issues
+}
+
+/// The trips costing more than the ratio of the cheapest sum of two trips through another station.
+pub(crate) fn detours(prices: &HashMap<PriceKey, Vec<Price>>, date: NaiveDate, ratio: f64) -> Vec<ConsistencyIssue> {
+    // the trips of each category and network between indexed stations
+    let mut grids: HashMap<(Category, Option<&String>, Currency), TripGrid> = HashMap::new();
+    for (key, history) in prices.iter().filter(|(key, _)| key.entry != key.exit) {
+        if let Some(price) = price_on(history, date).filter(|price| !price.price.is_zero()) {
+            grids.entry((key.category, key.network.as_ref(), price.price.currency())).or_default().add(&key.entry, &key.exit, price.price.minor());
+        }
+    }
+    let mut issues = Vec::new();
+    for ((category, network, currency), grid) in &grids {
+        let count = grid.stations.len();
+        for entry in 0..count {
+            // the cheapest price to each exit through another station, and this station
+            let mut cheapest_via: Vec<Option<(usize, i64)>> = vec![None; count];
+            for &(via, first_price) in &grid.exits[entry] {
+                for &(exit, second_price) in grid.exits[via].iter().filter(|(exit, _)| *exit != entry) {
+                    let via_price = first_price + second_price;
+                    if cheapest_via[exit].is_none_or(|(_, cheapest)| via_price < cheapest) {
+                        cheapest_via[exit] = Some((via, via_price));
+                    }
+                }
+            }
+            for &(exit, price) in &grid.exits[entry] {
+                let Some((via, via_price)) = cheapest_via[exit] else {
+                    continue;
+                };
+                if price as f64 > via_price as f64 * ratio {
+                    issues.push(ConsistencyIssue::Detour {
+                        key: PriceKey {
+                            entry: grid.stations[entry].to_string(),
+                            exit: grid.stations[exit].to_string(),
+                            category: *category,
+                            network: network.cloned()
+                        },
+                        price: Money::new(price, *currency).to_f64(),
+                        via: grid.stations[via].to_string(),
+                        via_price: Money::new(via_price, *currency).to_f64()
+                    });
+                }
+            }
+        }
+    }
+    issues
+}
+
+/// The prices of the trips between stations known by their index.
+#[derive(Default)]
+struct TripGrid<'a> {
+    stations: Vec<&'a String>,
+    indexes: HashMap<&'a String, usize>,
+    /// The exits of each entry, with their price in hundredths
+    exits: Vec<Vec<(usize, i64)>>
+}
+
+impl<'a> TripGrid<'a> {
+    fn index(&mut self, station: &'a String) -> usize {
+        if let Some(index) = self.indexes.get(station) {
+            return *index;
+        }
+        self.stations.push(station);
+        self.exits.push(Vec::new());
+        self.indexes.insert(station, self.stations.len() - 1);
+        self.stations.len() - 1
+    }
+
+    fn add(&mut self, entry: &'a String, exit: &'a String, price: i64) {
+        let entry = self.index(entry);
+        let exit = self.index(exit);
+        self.exits[entry].push((exit, price));
+    }
+}
+
+/// The trips whose price per km is more than the ratio above or below the median of their toll and category.
+pub(crate) fn per_km_outliers(toll_id: &str, trips: &[TollTrip], ratio: f64) -> Vec<ConsistencyIssue> {
+    let trips = trips.iter().filter(|trip| trip.distance >= MIN_DISTANCE && trip.price > 0.0).collect::<Vec<_>>();
+    let mut prices_per_km: HashMap<Category, Vec<f64>> = HashMap::new();
+    for trip in &trips {
+        prices_per_km.entry(trip.key.category).or_default().push(trip.price / trip.distance);
+    }
+    let medians = prices_per_km.into_iter()
+        .map(|(category, prices_per_km)| (category, median(prices_per_km)))
+        .collect::<HashMap<Category, f64>>();
+    trips.iter()
+        .filter_map(|trip| {
+            let median_per_km = medians[&trip.key.category];
+            let price_per_km = trip.price / trip.distance;
+            let outlier = price_per_km > median_per_km * ratio || price_per_km * ratio < median_per_km;
+            outlier.then(|| ConsistencyIssue::PerKmOutlier {
+                toll_id: toll_id.to_string(),
+                key: trip.key.clone(),
+                price: trip.price,
+                distance: trip.distance,
+                price_per_km,
+                median_per_km
+            })
+        })
+        .collect()
+}
+
+/// The prices changing by more than the percentage from the price of a previous year.
+pub(crate) fn yearly_changes(prices: &HashMap<PriceKey, Vec<Price>>, max_change: f64) -> Vec<ConsistencyIssue> {
+    let mut issues = Vec::new();
+    for (key, history) in prices {
+        // the used price of each validity date is the last one
+        let used_prices = history.iter()
+            .enumerate()
+            .filter(|(index, price)| history.get(index + 1).is_none_or(|next| next.effective_from() != price.effective_from()))
+            .map(|(_, price)| price)
+            .collect::<Vec<&Price>>();
+        for pair in used_prices.windows(2) {
+            let (previous, price) = (pair[0], pair[1]);
+            if previous.year >= price.year || previous.price.is_zero() || previous.price.currency() != price.price.currency() {
+                continue;
+            }
+            let percentage = (price.price.minor() - previous.price.minor()) as f64 / previous.price.minor() as f64 * 100.0;
+            if percentage.abs() > max_change {
+                issues.push(ConsistencyIssue::YearlyChange {
+                    key: key.clone(),
+                    previous_price: euros(previous),
+                    previous_file: previous.file.to_string(),
+                    price: euros(price),
+                    file: price.file.to_string(),
+                    percentage
+                });
+            }
+        }
+    }
+    issues
+}
+
+/// Great circle distance in km between two [longitude, latitude] locations.
+pub(crate) fn distance_km(from: [f64; 2], to: [f64; 2]) -> f64 {
+    let (from_longitude, from_latitude) = (from[0].to_radians(), from[1].to_radians());
+    let (to_longitude, to_latitude) = (to[0].to_radians(), to[1].to_radians());
+    let a = ((to_latitude - from_latitude) / 2.0).sin().powi(2)
+        + from_latitude.cos() * to_latitude.cos() * ((to_longitude - from_longitude) / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.0
+    } else {
+        values[middle]
+    }
+}
+
+fn euros(price: &Price) -> f64 {
+    price.price.to_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use chrono::NaiveDate;
+    use crate::category::Category;
+    use crate::money::{Currency, Money};
+    use crate::price::Price;
+    use crate::price_grid::PriceKey;
+    use super::{asymmetric_prices, detours, distance_km, yearly_changes, ConsistencyIssue};
+
+    fn key(entry: &str, exit: &str) -> PriceKey {
+        PriceKey {
+            entry: entry.to_string(),
+            exit: exit.to_string(),
+            category: Category::Car,
+            network: None
+        }
+    }
+
+    fn price(price: i64, year: u16) -> Price {
+        Price {
+            price: Money::new(price, Currency::EUR),
+            year,
+            file: format!("{}_ASF.tsv", year),
+            metadata: Arc::default()
+        }
+    }
+
+    #[test]
+    fn find_typos() {
+        let prices = HashMap::from([
+            (key("A", "B"), vec![price(100, 2022), price(150, 2023)]),
+            (key("B", "A"), vec![price(150, 2023)]),
+            (key("B", "C"), vec![price(200, 2023)]),
+            (key("A", "C"), vec![price(3500, 2023)]),
+            (key("C", "A"), vec![price(350, 2023)])
+        ]);
+        let date = NaiveDate::from_ymd_opt(2023, 6, 1).unwrap();
+        let asymmetric = asymmetric_prices(&prices, date);
+        assert_eq!(1, asymmetric.len());
+        assert!(matches!(&asymmetric[0], ConsistencyIssue::Asymmetric { key, .. } if key.entry == "A" && key.exit == "C"));
+        let detours = detours(&prices, date, 1.5);
+        assert_eq!(1, detours.len());
+        assert_eq!("Car A->C : 35.00 but 3.50 via B", detours[0].to_string());
+        let changes = yearly_changes(&prices, 10.0);
+        assert_eq!(1, changes.len());
+        assert_eq!("Car A->B : 1.00 in 2022_ASF.tsv -> 1.50 in 2023_ASF.tsv (+50.0%)", changes[0].to_string());
+    }
+
+    #[test]
+    fn distance() {
+        // Paris to Lyon
+        let distance = distance_km([2.3522, 48.8566], [4.8357, 45.7640]);
+        assert!((distance - 392.0).abs() < 1.0, "{}", distance);
+    }
+}
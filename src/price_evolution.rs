@@ -0,0 +1,164 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use serde::Serialize;
+use crate::category::Category;
+use crate::money::Money;
+use crate::price::Price;
+use crate::price_grid::PriceKey;
+
+/// The change of the prices of a file family and category between two years.
+#[derive(Serialize)]
+pub struct PriceEvolution {
+    pub operator: String,
+    /// The name of the files without their year prefix
+    pub family: String,
+    pub category: Category,
+    pub from_year: u16,
+    pub to_year: u16,
+    /// Number of trips priced both years
+    pub compared: usize,
+    /// Average, min and max increase in percent of the compared trips, None when no trip is compared
+    pub average: Option<f64>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// The trips only priced the first year
+    pub vanished: Vec<PriceKey>,
+    /// The trips only priced the second year
+    pub appeared: Vec<PriceKey>
+}
+
+impl fmt::Display for PriceEvolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {}->{} : {} trips", self.operator, self.family, self.category, self.from_year, self.to_year, self.compared)?;
+        if let (Some(average), Some(min), Some(max)) = (self.average, self.min, self.max) {
+            write!(f, ", average {:+.2}%, min {:+.2}%, max {:+.2}%", average, min, max)?;
+        }
+        write!(f, ", {} vanished, {} appeared", self.vanished.len(), self.appeared.len())
+    }
+}
+
+/// The family of a price file, its name without the year prefix : 2023_ASF-A20.tsv -> ASF-A20.tsv
+pub fn file_family(file: &str) -> &str {
+    match file.split_once('_') {
+        Some((year, family)) if year.len() == 4 && year.chars().all(|c| c.is_ascii_digit()) => family,
+        _ => file
+    }
+}
+
+/// The operator of a price, declared in its metadata or else the first word of its file family.
+fn operator(price: &Price) -> String {
+    price.metadata.operator.clone().unwrap_or_else(|| {
+        file_family(&price.file)
+            .split(['-', '_', ' ', ',', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_uppercase()
+    })
+}
+
+/// The price of each trip of a file family and category, by year.
+type FamilyPrices<'a> = BTreeMap<u16, HashMap<&'a PriceKey, Money>>;
+
+/// Compare the prices of each file family between its consecutive years.
+pub(crate) fn price_evolution(prices: &HashMap<PriceKey, Vec<Price>>) -> Vec<PriceEvolution> {
+    let mut families: BTreeMap<(String, &str, Category), FamilyPrices> = BTreeMap::new();
+    for (key, history) in prices {
+        for price in history {
+            families.entry((operator(price), file_family(&price.file), key.category))
+                .or_default()
+                .entry(price.year)
+                .or_default()
+                .insert(key, price.price);
+        }
+    }
+    let mut evolutions = Vec::new();
+    for ((operator, family, category), years) in &families {
+        let years = years.iter().collect::<Vec<_>>();
+        for pair in years.windows(2) {
+            let ((from_year, from_prices), (to_year, to_prices)) = (pair[0], pair[1]);
+            let increases = from_prices.iter()
+                .filter(|(_, from_price)| !from_price.is_zero())
+                .filter_map(|(key, from_price)| {
+                    let to_price = to_prices.get(key).filter(|to_price| to_price.currency() == from_price.currency())?;
+                    Some((to_price.minor() - from_price.minor()) as f64 / from_price.minor() as f64 * 100.0)
+                })
+                .collect::<Vec<f64>>();
+            let compared = from_prices.keys().filter(|key| to_prices.contains_key(*key)).count();
+            evolutions.push(PriceEvolution {
+                operator: operator.to_string(),
+                family: family.to_string(),
+                category: *category,
+                from_year: *from_year,
+                to_year: *to_year,
+                compared,
+                average: (!increases.is_empty()).then(|| increases.iter().sum::<f64>() / increases.len() as f64),
+                min: increases.iter().copied().reduce(f64::min),
+                max: increases.iter().copied().reduce(f64::max),
+                vanished: only_in(from_prices, to_prices),
+                appeared: only_in(to_prices, from_prices)
+            });
+        }
+    }
+    evolutions
+}
+
+/// The sorted trips of the first year missing from the other year.
+fn only_in(prices: &HashMap<&PriceKey, Money>, other_prices: &HashMap<&PriceKey, Money>) -> Vec<PriceKey> {
+    prices.keys()
+        .filter(|key| !other_prices.contains_key(*key))
+        .map(|key| (*key).clone())
+        .collect::<BTreeSet<PriceKey>>()
+        .into_iter()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use crate::category::Category;
+    use crate::money::{Currency, Money};
+    use crate::price::Price;
+    use crate::price_grid::PriceKey;
+    use super::{file_family, price_evolution};
+
+    fn key(exit: &str) -> PriceKey {
+        PriceKey {
+            entry: "A".to_string(),
+            exit: exit.to_string(),
+            category: Category::Car,
+            network: None
+        }
+    }
+
+    fn price(price: i64, year: u16) -> Price {
+        Price {
+            price: Money::new(price, Currency::EUR),
+            year,
+            file: format!("{}_ASF-A89-page8-Car.tsv", year),
+            metadata: Arc::default()
+        }
+    }
+
+    #[test]
+    fn evolution() {
+        assert_eq!("ASF-A20.tsv", file_family("2023_ASF-A20.tsv"));
+        assert_eq!("ASF-A89-OUEST.tsv", file_family("ASF-A89-OUEST.tsv"));
+        let prices = HashMap::from([
+            (key("B"), vec![price(100, 2022), price(110, 2023)]),
+            (key("C"), vec![price(200, 2022), price(202, 2023)]),
+            (key("D"), vec![price(300, 2022)]),
+            (key("E"), vec![price(400, 2023)])
+        ]);
+        let evolutions = price_evolution(&prices);
+        assert_eq!(1, evolutions.len());
+        let evolution = &evolutions[0];
+        assert_eq!("ASF", evolution.operator);
+        assert_eq!(2, evolution.compared);
+        assert_eq!(Some(1.0), evolution.min);
+        assert_eq!(Some(10.0), evolution.max);
+        assert_eq!(vec![key("D")], evolution.vanished);
+        assert_eq!(vec![key("E")], evolution.appeared);
+        assert_eq!("ASF ASF-A89-page8-Car.tsv Car 2022->2023 : 2 trips, average +5.50%, min +1.00%, max +10.00%, 1 vanished, 1 appeared", evolution.to_string());
+    }
+}
@@ -1,81 +1,117 @@
-use std::fs;
-use enum_iterator::all;
+use std::io;
 use crate::category::Category;
 use crate::io_tools::read_lines;
+use crate::money::{Currency, Money};
+use crate::name_normalizer::NameScope;
 use crate::price::Price;
+use crate::price_grid::flat_profile::FlatProfile;
 use crate::price_grid::price_load_audit::{PriceLoadAudit, PriceLoadError};
-use crate::price_grid::{FlatFileName, PriceKey, PriceLoader};
+use crate::price_grid::{file_name, io_error, price_files, FlatColumns, FlatFileName, PriceKey, PriceLoader};
 
 impl<'a> PriceLoader<'a> {
     pub(crate) fn load_flat(&mut self) -> PriceLoadAudit {
         println!("Loading flat prices");
         let mut audit = PriceLoadAudit ::new();
-        const FLAT_PRICES_FOLDER: &'static str = "prices/flat";
-        if let Ok(paths) = fs::read_dir(FLAT_PRICES_FOLDER) {
+        let flat_prices_folder = self.paths.flat_dir.as_path();
+        if let Ok(paths) = price_files(flat_prices_folder) {
             for path in paths {
-                let path = path.unwrap().path();
-                // 2020_APRR-1,2,4,8.tsv
-                let file_name = path.clone();
-                let file_name = file_name.file_name().unwrap().to_str().unwrap();
-                let flat_file_name = FlatFileName::new(file_name).unwrap();
+                let file_name = file_name(&path);
+                let Some(metadata) = self.load_metadata(&mut audit, &path, &file_name) else {
+                    continue;
+                };
+                let flat_file_name = FlatFileName::new(&file_name, metadata);
                 println!("Loading {} -> year {}", file_name, flat_file_name.year);
-                let mut skipped_header = false;
-                if let Ok(lines) = read_lines(path) {
-                    for line in lines {
-                        if !skipped_header {
-                            skipped_header = true;
+                let lines = match read_lines(&path).and_then(|lines| lines.collect::<io::Result<Vec<String>>>()) {
+                    Ok(lines) => lines,
+                    Err(error) => {
+                        audit.error.push(io_error(&file_name, error));
+                        continue;
+                    }
+                };
+                let Some(header) = lines.first() else {
+                    continue;
+                };
+                let header = header.split('\t').collect::<Vec<&str>>();
+                let (columns, skip) = if let Some((operator, columns)) = FlatProfile::resolve_header(&flat_file_name.operator, &header) {
+                    println!("Using {} header of {}", operator, file_name);
+                    (columns, 1)
+                } else {
+                    match flat_file_name.columns() {
+                        Ok(columns) => {
+                            // without a known header the first line is either an unknown header or already a price
+                            let skip = if is_price_line(&header, &columns) { 0 } else { 1 };
+                            (columns, skip)
+                        }
+                        Err(cause) => {
+                            println!("Skipping {} : {}", file_name, cause);
+                            audit.error.push(PriceLoadError::InvalidFileName {
+                                file: file_name.to_string(),
+                                cause
+                            });
+                            continue;
+                        }
+                    }
+                };
+                self.file_scope(&file_name, Some(&flat_file_name.operator), flat_file_name.metadata.network.as_deref());
+                for (line_index, line) in lines.iter().enumerate().skip(skip) {
+                    let tokens = line.split('\t').collect::<Vec<&str>>();
+                    for (category, price_index) in &columns.price_indexes {
+                        if flat_file_name.metadata.category.is_some_and(|declared_category| declared_category != *category) {
                             continue;
                         }
-                        let line = line.unwrap();
-                        let tokens = line.split("\t").collect::<Vec<&str>>();
-                        let categories = all::<Category>().collect::<Vec<_>>();
-                        for category in categories {
-                            if let Ok(result) = self.get_flat_price(&tokens, &flat_file_name, category) {
-                                let key = result.0;
-                                let value = result.1;
-                                self.insert_price(&mut audit, file_name, &key.entry, &key.exit, category, value.price, value.year);
-                            } else {
-                                let error = PriceLoadError {
-                                    file_name: file_name.to_string(),
-                                    line: line.to_string(),
-                                    error: format!("Invalid line {} for {}", line, category)
-                                };
-                                audit.error.push(error);
-                            }
+                        match self.get_flat_price(&tokens, &flat_file_name, &columns, *category, *price_index, line_index + 1) {
+                            Ok((key, value)) => self.insert_price(&mut audit, key, value),
+                            Err(error) => audit.error.push(error)
                         }
                     }
                 }
             }
         } else {
-            println!("Directory {} not found", FLAT_PRICES_FOLDER);
+            println!("Directory {} not found", flat_prices_folder.display());
         }
         audit
     }
 
-    fn get_flat_price(&self, tokens: &Vec<&str>, flat_file_name: &FlatFileName, category: Category) -> Result<(PriceKey, Price), String> {
-        let entry = self.name_normalizer.normalize(tokens[flat_file_name.entry_index]);
-        let exit = self.name_normalizer.normalize(tokens[flat_file_name.exit_index]);
+    fn get_flat_price(&self, tokens: &[&str], flat_file_name: &FlatFileName, columns: &FlatColumns, category: Category, price_index: usize, line: usize) -> Result<(PriceKey, Price), PriceLoadError> {
+        let max_index = columns.entry_index.max(columns.exit_index).max(price_index);
+        if tokens.len() <= max_index {
+            return Err(PriceLoadError::InvalidLine {
+                file: flat_file_name.file.clone(),
+                line,
+                cause: format!("missing column {} for {}", max_index + 1, category)
+            });
+        }
+        let scope = NameScope::for_file(&flat_file_name.file, Some(&flat_file_name.operator), flat_file_name.metadata.network.as_deref());
+        let entry = self.name_normalizer.normalize_in(tokens[columns.entry_index], &scope);
+        let exit = self.name_normalizer.normalize_in(tokens[columns.exit_index], &scope);
         let key = PriceKey {
             entry,
             exit,
-            category
-        };
-        let price_index = match category {
-            Category::Car => {flat_file_name.car_index}
-            Category::Motorcycle => {flat_file_name.motorcycle_index}
+            category,
+            network: flat_file_name.metadata.network.clone()
         };
 
-        if let Ok(price_value) = tokens[price_index].replace(',',".").parse::<f32>() {
-            let price_value = (price_value * 100.) as u16;
-
+        if let Ok(price_value) = Money::parse(tokens[price_index], flat_file_name.metadata.price_currency()) {
             let price = Price {
                 price: price_value,
                 year: flat_file_name.year,
-                file: flat_file_name.file.clone()
+                file: flat_file_name.file.clone(),
+                metadata: flat_file_name.metadata.clone()
             };
             Ok((key, price))
         } else {
-            return Err(format!("Invalid price value {} for {}", tokens[price_index], key.entry));
+            Err(PriceLoadError::InvalidPrice {
+                file: flat_file_name.file.clone(),
+                line,
+                column: price_index + 1,
+                value: tokens[price_index].to_string()
+            })
         }
     }
-}
\ No newline at end of file
+}
+
+fn is_price_line(tokens: &[&str], columns: &FlatColumns) -> bool {
+    columns.price_indexes.iter().all(|(_, index)| {
+        tokens.get(*index).is_some_and(|token| Money::parse(token, Currency::EUR).is_ok())
+    })
+}
@@ -0,0 +1,105 @@
+use enum_iterator::all;
+use unidecode::unidecode;
+use crate::category::Category;
+use crate::price_grid::FlatColumns;
+
+/// Header names used by an operator in its flat price files.
+pub(crate) struct FlatProfile {
+    pub(crate) operator: &'static str,
+    entry: &'static [&'static str],
+    exit: &'static [&'static str],
+    class_prefixes: &'static [&'static str]
+}
+
+// Cofiroute comes before Sanef as its header also contains the "Autoroute entrée" and "Péage entrée" columns
+const PROFILES: [FlatProfile; 4] = [
+    FlatProfile {
+        operator: "APRR",
+        entry: &["GARE D'ENTREE", "GARE ENTREE", "ENTREE"],
+        exit: &["GARE DE SORTIE", "GARE SORTIE", "SORTIE"],
+        class_prefixes: &["CLASSE ", "CL"]
+    },
+    FlatProfile {
+        operator: "AREA",
+        entry: &["GARE D'ENTREE"],
+        exit: &["GARE DE SORTIE"],
+        class_prefixes: &["CLASSE "]
+    },
+    FlatProfile {
+        operator: "COFIROUTE",
+        entry: &["VILLE ENTREE"],
+        exit: &["VILLE SORTIE"],
+        class_prefixes: &["CLASSE "]
+    },
+    FlatProfile {
+        operator: "SANEF",
+        entry: &["AUTOROUTE ENTREE"],
+        exit: &["PEAGE ENTREE"],
+        class_prefixes: &["CLASSE "]
+    }
+];
+
+impl FlatProfile {
+    /// Find the columns of a flat file from its header, trying the profile of the given operator first.
+    pub(crate) fn resolve_header(operator: &str, header: &[&str]) -> Option<(&'static str, FlatColumns)> {
+        let operator = operator.to_uppercase();
+        let header = header.iter().map(|name| normalize_column(name)).collect::<Vec<String>>();
+        PROFILES.iter()
+            .filter(|profile| profile.operator == operator)
+            .chain(PROFILES.iter().filter(|profile| profile.operator != operator))
+            .find_map(|profile| profile.resolve(&header).map(|columns| (profile.operator, columns)))
+    }
+
+    fn resolve(&self, header: &[String]) -> Option<FlatColumns> {
+        let entry_index = find_column(header, self.entry)?;
+        let exit_index = find_column(header, self.exit)?;
+        let price_indexes = all::<Category>()
+            .filter_map(|category| {
+                let names = self.class_prefixes.iter()
+                    .map(|prefix| format!("{}{}", prefix, category.class()))
+                    .collect::<Vec<String>>();
+                header.iter()
+                    .position(|column| names.contains(column))
+                    .map(|index| (category, index))
+            })
+            .collect::<Vec<(Category, usize)>>();
+        if price_indexes.is_empty() {
+            return None;
+        }
+        Some(FlatColumns {
+            entry_index,
+            exit_index,
+            price_indexes
+        })
+    }
+}
+
+fn find_column(header: &[String], names: &[&str]) -> Option<usize> {
+    names.iter().find_map(|name| header.iter().position(|column| column == name))
+}
+
+fn normalize_column(name: &str) -> String {
+    unidecode(name).trim().to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::category::Category;
+    use super::FlatProfile;
+
+    #[test]
+    fn resolve_cofiroute_header() {
+        let header = ["Autoroute entrée", "Péage entrée", "Ville entrée", "Autoroute sortie", "Péage sortie", "Ville sortie", "Classe 1", "Classe 2", "Classe 3", "Classe 4", "Classe 5"];
+        let (operator, columns) = FlatProfile::resolve_header("", &header).unwrap();
+        assert_eq!("COFIROUTE", operator);
+        assert_eq!(2, columns.entry_index);
+        assert_eq!(5, columns.exit_index);
+        assert_eq!(vec![(Category::Car, 6), (Category::Intermediate, 7), (Category::TwoAxleTruck, 8), (Category::ThreeAxleTruck, 9), (Category::Motorcycle, 10)], columns.price_indexes);
+    }
+
+    #[test]
+    fn unknown_header() {
+        let header = ["ALLAINES", "AMBERIEU", "462,61", "54,20"];
+        assert!(FlatProfile::resolve_header("APRR", &header).is_none());
+    }
+}
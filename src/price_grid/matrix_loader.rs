@@ -1,58 +1,86 @@
-use std::fs;
-use std::fs::DirEntry;
+use std::path::PathBuf;
 use crate::category::Category;
-use crate::io_tools::{is_dir, read_lines_tokens};
+use crate::io_tools::read_lines_tokens;
+use crate::money::Money;
+use crate::price::Price;
 use crate::price_grid::price_load_audit::{PriceLoadAudit, PriceLoadError};
-use crate::price_grid::{get_year, PriceLoader};
+use crate::price_grid::{file_name, get_year, io_error, price_files, PriceKey, PriceLoader};
 
 impl<'a> PriceLoader<'a> {
     pub(crate) fn load_matrix(&mut self, category: Category) -> PriceLoadAudit {
         println!("Loading matrix {}", category);
-        let path = format!("prices/matrix/{}", category.to_string().to_lowercase());
+        let path = self.paths.matrix_category_dir(category);
         let mut audit = PriceLoadAudit::new();
-        if is_dir(&path) {
-            let paths = fs::read_dir(path).unwrap();
+        if path.is_dir() {
+            let paths = price_files(&path).unwrap_or_default();
             for path in paths {
-                let dir_entry = path.unwrap();
-                let new_audit = self.load_matrix_file(category, dir_entry);
+                let new_audit = self.load_matrix_file(category, path);
                 audit.merge(&new_audit);
             }
         } else {
-            println!("Directory {} not found", path);
+            println!("Directory {} not found", path.display());
         }
         audit
     }
 
-    fn load_matrix_file(&mut self, category: Category, dir_entry: DirEntry) -> PriceLoadAudit {
-        let path = dir_entry.path();
-        let file_name = path.clone();
-        let file_name = file_name.file_name().unwrap().to_str().unwrap();
-        let year = get_year(&file_name);
-        println!("Loading matrix {} -> year {}", file_name, year);
+    fn load_matrix_file(&mut self, category: Category, path: PathBuf) -> PriceLoadAudit {
+        let file_name = file_name(&path);
         let mut audit = PriceLoadAudit::new();
-        if let Ok(tokenized_lines) = read_lines_tokens(path) {
-            let header_line_tokens = &tokenized_lines[0];
-            for row in 1..tokenized_lines.len() {
-                let line_token = &tokenized_lines[row];
-                let entry = self.name_normalizer.normalize(&line_token[0]);
-                if line_token.len() != header_line_tokens.len() {
-                    println!("Invalid line length for {}", entry);
-                    let error = PriceLoadError {
-                        file_name: file_name.to_string(),
-                        line: "".to_string(),
-                        error: "Invalid line length".to_string()
-                    };
-                    audit.error.push(error);
+        let Some(metadata) = self.load_metadata(&mut audit, &path, &file_name) else {
+            return audit;
+        };
+        let year = metadata.year().unwrap_or_else(|| get_year(&file_name));
+        let category = metadata.category.unwrap_or(category);
+        println!("Loading matrix {} -> year {}", file_name, year);
+        self.matrix_files.insert(file_name.to_string());
+        let tokenized_lines = match read_lines_tokens(path) {
+            Ok(tokenized_lines) => tokenized_lines,
+            Err(error) => {
+                audit.error.push(io_error(&file_name, error));
+                return audit;
+            }
+        };
+        let Some(header_line_tokens) = tokenized_lines.first() else {
+            return audit;
+        };
+        let scope = self.file_scope(&file_name, metadata.operator.as_deref(), metadata.network.as_deref());
+        for (line_index, line_token) in tokenized_lines.iter().enumerate().skip(1) {
+            let entry = self.name_normalizer.normalize_in(&line_token[0], &scope);
+            if line_token.len() != header_line_tokens.len() {
+                println!("Invalid line length for {}", entry);
+                audit.error.push(PriceLoadError::InvalidLine {
+                    file: file_name.to_string(),
+                    line: line_index + 1,
+                    cause: format!("{} columns instead of {}", line_token.len(), header_line_tokens.len())
+                });
+                continue;
+            }
+            for column in 1..line_token.len() {
+                let exit = self.name_normalizer.normalize_in(&header_line_tokens[column], &scope);
+                let Ok(price) = Money::parse(&line_token[column], metadata.price_currency()) else {
+                    audit.error.push(PriceLoadError::InvalidPrice {
+                        file: file_name.to_string(),
+                        line: line_index + 1,
+                        column: column + 1,
+                        value: line_token[column].to_string()
+                    });
                     continue;
-                }
-                for column in 1..line_token.len() {
-                    let exit = self.name_normalizer.normalize(&header_line_tokens[column]);
-                    let price = line_token[column].replace(',', ".");
-                    let price = (price.parse::<f32>().unwrap() * 100.) as u16;
-                    self.insert_price(&mut audit, file_name, &entry, &exit, category, price, year)
-                }
+                };
+                let key = PriceKey {
+                    entry: entry.to_string(),
+                    exit,
+                    category,
+                    network: metadata.network.clone()
+                };
+                let price = Price {
+                    price,
+                    year,
+                    file: file_name.to_string(),
+                    metadata: metadata.clone()
+                };
+                self.insert_price(&mut audit, key, price)
             }
         }
         audit
     }
-}
\ No newline at end of file
+}
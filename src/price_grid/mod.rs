@@ -1,117 +1,259 @@
 mod price_load_audit;
 mod flat_loader;
+mod flat_profile;
 mod triangle_loader;
 mod matrix_loader;
+mod plaza_loader;
+mod price_metadata;
+mod price_precedence;
 
-use std::collections::HashMap;
-use std::{fmt};
+use std::collections::{HashMap, HashSet};
+use std::{fmt, fs, io};
 use std::fmt::Formatter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use enum_iterator::all;
+use serde::Serialize;
 use crate::{DEFAULT_YEAR};
 use crate::category::Category;
-use crate::name_normalizer::NameNormalizer;
+use crate::name_normalizer::{NameNormalizer, NameScope};
+use crate::price_paths::PricePaths;
 use crate::price::Price;
-use crate::price_grid::price_load_audit::{PriceLoadAudit};
+pub use crate::price_grid::price_load_audit::{ConflictPrice, PriceConflict, PriceLoadAudit, PriceLoadError, SymmetryConflict};
+pub use crate::price_grid::price_metadata::{PriceMetadata, TriangleDirection};
+pub use crate::price_grid::price_precedence::PricePrecedence;
 
-#[derive(Eq, PartialEq, Hash)]
-pub(crate) struct PriceKey {
-    pub(crate) entry: String,
-    pub(crate) exit: String,
-    pub(crate) category: Category
+/// A trip between two normalized station names for a vehicle category.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Debug, Serialize)]
+pub struct PriceKey {
+    pub entry: String,
+    pub exit: String,
+    pub category: Category,
+    /// The motorway or network of the stations, telling apart homonymous stations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>
+}
+
+impl PriceKey {
+    /// The key of the fixed price of a plaza, among the plaza prices.
+    pub fn plaza(plaza: &str, category: Category, network: Option<String>) -> PriceKey {
+        PriceKey {
+            entry: plaza.to_string(),
+            exit: plaza.to_string(),
+            category,
+            network
+        }
+    }
 }
 
 struct FlatFileName {
     year: u16,
+    operator: String,
+    file: String,
+    metadata: Arc<PriceMetadata>
+}
+
+struct FlatColumns {
     entry_index: usize,
     exit_index: usize,
-    car_index: usize,
-    motorcycle_index: usize,
-    file: String
+    price_indexes: Vec<(Category, usize)>
 }
 
 pub(crate) struct PriceLoader<'a> {
+    pub(crate) paths: &'a PricePaths,
     pub(crate) name_normalizer: &'a NameNormalizer,
-    pub(crate) prices: HashMap<PriceKey, Price>
+    pub(crate) prices: HashMap<PriceKey, Vec<Price>>,
+    /// The fixed prices of the plazas, apart from the trips
+    pub(crate) plaza_prices: HashMap<PriceKey, Vec<Price>>,
+    /// The triangle files declared symmetric
+    pub(crate) symmetric_files: HashSet<String>,
+    pub(crate) matrix_files: HashSet<String>,
+    /// The name scope of every loaded price file
+    pub(crate) file_scopes: Vec<NameScope>
 }
 
 impl<'a> PriceLoader<'a> {
     pub(crate) fn load_prices(&mut self) -> PriceLoadAudit {
-         let mut audit = PriceLoadAudit {
-            loaded_cars: 0,
-            loaded_motorcycles: 0,
-            error: Vec::new()
-        };
+        let mut audit = PriceLoadAudit::new();
         let new_audit = self.load_flat();
         audit.merge(&new_audit);
-        let new_audit = self.load_matrix(Category::Car);
-        audit.merge(&new_audit);
-        let new_audit = self.load_matrix(Category::Motorcycle);
-        audit.merge(&new_audit);
-        let new_audit = self.load_triangles(Category::Car);
-        audit.merge(&new_audit);
-        let new_audit = self.load_triangles(Category::Motorcycle);
+        for category in all::<Category>() {
+            let new_audit = self.load_matrix(category);
+            audit.merge(&new_audit);
+        }
+        for category in all::<Category>() {
+            let new_audit = self.load_triangles(category);
+            audit.merge(&new_audit);
+        }
+        let new_audit = self.load_plazas();
         audit.merge(&new_audit);
+        self.check_symmetry(&mut audit);
+        let precedence = match PricePrecedence::load(&self.paths.precedence_file) {
+            Ok(precedence) => precedence,
+            Err(cause) => {
+                println!("{}, using the file name order", cause);
+                audit.error.push(PriceLoadError::InvalidMetadata {
+                    file: file_name(&self.paths.precedence_file),
+                    cause
+                });
+                PricePrecedence::default()
+            }
+        };
+        precedence.resolve(&mut self.prices, &mut audit);
+        precedence.resolve(&mut self.plaza_prices, &mut audit);
         audit
     }
 
-    fn insert_price(&mut self, audit: &mut PriceLoadAudit, file: &str, entry: &String, exit: &String, category: Category, price: u16, year: u16) {
-        let key = PriceKey {
-            entry: entry.to_string(),
-            exit: exit.to_string(),
-            category
-        };
-        let existing_prices = self.prices.get(&key);
-        if existing_prices.is_some() {
-            let existing_price = existing_prices.unwrap();
-            if existing_price.year > year {
-                // the existing price is more recent, skip
-                return;
+    /// Add a price to the history of its key.
+    fn insert_price(&mut self, audit: &mut PriceLoadAudit, key: PriceKey, price: Price) {
+        audit.add_loaded(key.category, &price.file);
+        insert_sorted(self.prices.entry(key).or_default(), price);
+    }
+
+    /// Record the matrix prices differing from the price of a symmetric triangle for the same trip and validity date.
+    fn check_symmetry(&self, audit: &mut PriceLoadAudit) {
+        let mut conflicts = Vec::new();
+        for (key, history) in &self.prices {
+            let triangle_prices = history.iter().filter(|price| self.symmetric_files.contains(&price.file));
+            for triangle_price in triangle_prices {
+                let matrix_prices = history.iter().filter(|price| {
+                    self.matrix_files.contains(&price.file)
+                        && price.effective_from() == triangle_price.effective_from()
+                        && price.price != triangle_price.price
+                });
+                for matrix_price in matrix_prices {
+                    conflicts.push(SymmetryConflict {
+                        key: key.clone(),
+                        effective_from: triangle_price.effective_from(),
+                        matrix: ConflictPrice::new(matrix_price),
+                        triangle: ConflictPrice::new(triangle_price)
+                    });
+                }
             }
         }
-        let price = Price {
-            price,
-            year,
-            file: file.to_string()
-        };
+        conflicts.sort_by(|first, second| {
+            (&first.key.entry, &first.key.exit, first.key.category, first.effective_from, &first.matrix.file)
+                .cmp(&(&second.key.entry, &second.key.exit, second.key.category, second.effective_from, &second.matrix.file))
+        });
+        audit.symmetry_conflicts.extend(conflicts);
+    }
 
-        match category {
-            Category::Car => audit.loaded_cars += 1,
-            Category::Motorcycle => audit.loaded_motorcycles += 1
+    /// The scope of the names of a price file, kept to apply its aliases to the sections of the tolls of its motorways.
+    fn file_scope(&mut self, file_name: &str, operator: Option<&str>, network: Option<&str>) -> NameScope {
+        let scope = NameScope::for_file(file_name, operator, network);
+        self.file_scopes.push(scope.clone());
+        scope
+    }
+
+    /// Load the sidecar metadata of a price file, reporting invalid manifests in the audit.
+    fn load_metadata(&self, audit: &mut PriceLoadAudit, path: &Path, file_name: &str) -> Option<Arc<PriceMetadata>> {
+        match PriceMetadata::load(path) {
+            Ok(metadata) => Some(Arc::new(metadata)),
+            Err(cause) => {
+                println!("Skipping {} : {}", file_name, cause);
+                audit.error.push(PriceLoadError::InvalidMetadata {
+                    file: file_name.to_string(),
+                    cause
+                });
+                None
+            }
         }
-        self.prices.insert(key, price);
     }
 }
 
 impl FlatFileName {
-    fn new(file_name: &str) -> Result<FlatFileName, String> {
-        let end = file_name.find("-").unwrap();
-        // suffix : 1,2,4,8
-        let suffix = &file_name[end + 1..].strip_suffix(".tsv").unwrap();
+    fn new(file_name: &str, metadata: Arc<PriceMetadata>) -> FlatFileName {
+        // 2023_APRR-1,2,4,8.tsv or 2023_APRR.tsv
+        let stem = file_name.strip_suffix(".tsv").unwrap_or(file_name);
+        let stem = stem.split_once('_').map(|(_, operator)| operator).unwrap_or(stem);
+        let operator = stem.split('-').next().unwrap_or(stem);
+        FlatFileName {
+            year: metadata.year().unwrap_or_else(|| get_year(file_name)),
+            operator: metadata.operator.clone().unwrap_or(operator.to_string()),
+            file: file_name.to_string(),
+            metadata
+        }
+    }
+
+    /// Columns encoded in the file name suffix, used when the header is not recognized.
+    fn columns(&self) -> Result<FlatColumns, String> {
+        let end = self.file.find('-').ok_or(format!("No column suffix in {}", self.file))?;
+        // suffix : 1,2,4,8 (entry, exit, class 1, class 5) or 1,2,3,4,5,6,7 (entry, exit, classes 1 to 5)
+        let suffix = self.file[end + 1..].strip_suffix(".tsv").unwrap_or(&self.file[end + 1..]);
         let tokens = suffix
-            .split(",")
-            .map(|s| s.parse::<u8>().unwrap() - 1)
-            .map(|i| i as usize)
-            .collect::<Vec<usize>>();
+            .split(',')
+            .map(|s| s.parse::<usize>().ok().filter(|index| *index > 0).map(|index| index - 1))
+            .collect::<Option<Vec<usize>>>()
+            .ok_or(format!("Invalid column suffix {} in {}", suffix, self.file))?;
 
+        let price_indexes = match tokens.len() {
+            4 => {
+                let car_index = tokens[2];
+                let motorcycle_index = tokens[3];
+                if motorcycle_index == car_index + 4 {
+                    // classes 1 to 5 are in consecutive columns
+                    all::<Category>().map(|category| (category, car_index + category.class() as usize - 1)).collect()
+                } else {
+                    vec![(Category::Car, car_index), (Category::Motorcycle, motorcycle_index)]
+                }
+            }
+            7 => all::<Category>().map(|category| (category, tokens[category.class() as usize + 1])).collect(),
+            _ => return Err(format!("Invalid column suffix {} in {}", suffix, self.file))
+        };
 
-        let flat_file_name = FlatFileName {
-            year: get_year(&file_name),
+        Ok(FlatColumns {
             entry_index: tokens[0],
             exit_index: tokens[1],
-            car_index: tokens[2],
-            motorcycle_index: tokens[3],
-            file: file_name.to_string()
-        };
-        Ok(flat_file_name)
+            price_indexes
+        })
     }
 }
 
 impl fmt::Display for PriceKey {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{} {}->{}", self.category, self.entry, self.exit)
+        write!(f, "{} {}->{}", self.category, self.entry, self.exit)?;
+        if let Some(network) = &self.network {
+            write!(f, " on {}", network)?;
+        }
+        Ok(())
     }
 }
 
+/// Price files of a directory, sorted by name, ignoring the metadata manifests.
+fn price_files(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "tsv"))
+        .collect::<Vec<PathBuf>>();
+    files.sort();
+    Ok(files)
+}
+
+/// Insert a price in a history sorted by validity date then file name.
+fn insert_sorted(history: &mut Vec<Price>, price: Price) {
+    let effective_from = price.effective_from();
+    let index = history.partition_point(|existing_price| {
+        (existing_price.effective_from(), &existing_price.file) <= (effective_from, &price.file)
+    });
+    history.insert(index, price);
+}
+
 fn get_year(file_name: &str) -> u16 {
-    let year = file_name[0..4].parse::<u16>().unwrap_or_else(|_| DEFAULT_YEAR);
-    year
+    file_name.get(0..4)
+        .and_then(|year| year.parse::<u16>().ok())
+        .unwrap_or(DEFAULT_YEAR)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn io_error(file_name: &str, error: io::Error) -> PriceLoadError {
+    println!("Unable to read {} : {}", file_name, error);
+    PriceLoadError::Io {
+        file: file_name.to_string(),
+        cause: error.to_string()
+    }
 }
@@ -0,0 +1,117 @@
+use std::path::PathBuf;
+use crate::category::Category;
+use crate::io_tools::read_lines_tokens;
+use crate::money::Money;
+use crate::price::Price;
+use crate::price_grid::price_load_audit::{PriceLoadAudit, PriceLoadError};
+use crate::price_grid::{file_name, get_year, insert_sorted, io_error, price_files, PriceKey, PriceLoader};
+
+impl<'a> PriceLoader<'a> {
+    pub(crate) fn load_plazas(&mut self) -> PriceLoadAudit {
+        println!("Loading plaza prices");
+        let path = self.paths.plaza_dir.clone();
+        let mut audit = PriceLoadAudit::new();
+        if path.is_dir() {
+            let paths = price_files(&path).unwrap_or_default();
+            for path in paths {
+                let new_audit = self.load_plaza_file(path);
+                audit.merge(&new_audit);
+            }
+        } else {
+            println!("Directory {} not found", path.display());
+        }
+        audit
+    }
+
+    /// A plaza file has a header naming the category of each price column, the first column being the plaza name.
+    fn load_plaza_file(&mut self, path: PathBuf) -> PriceLoadAudit {
+        let file_name = file_name(&path);
+        let mut audit = PriceLoadAudit::new();
+        let Some(metadata) = self.load_metadata(&mut audit, &path, &file_name) else {
+            return audit;
+        };
+        let year = metadata.year().unwrap_or_else(|| get_year(&file_name));
+        println!("Loading plaza {} -> year {}", file_name, year);
+        let tokenized_lines = match read_lines_tokens(path) {
+            Ok(tokenized_lines) => tokenized_lines,
+            Err(error) => {
+                audit.error.push(io_error(&file_name, error));
+                return audit;
+            }
+        };
+        let Some(header_line_tokens) = tokenized_lines.first() else {
+            return audit;
+        };
+        let categories = match header_line_tokens.iter().skip(1).map(|name| Category::from_name(name).ok_or(name)).collect::<Result<Vec<Category>, &String>>() {
+            Ok(categories) => categories,
+            Err(name) => {
+                println!("Skipping {} : unknown category {}", file_name, name);
+                audit.error.push(PriceLoadError::InvalidLine {
+                    file: file_name.to_string(),
+                    line: 1,
+                    cause: format!("unknown category {}", name)
+                });
+                return audit;
+            }
+        };
+        let scope = self.file_scope(&file_name, metadata.operator.as_deref(), metadata.network.as_deref());
+        for (line_index, line_token) in tokenized_lines.iter().enumerate().skip(1) {
+            if line_token.len() != header_line_tokens.len() {
+                audit.error.push(PriceLoadError::InvalidLine {
+                    file: file_name.to_string(),
+                    line: line_index + 1,
+                    cause: format!("{} columns instead of {}", line_token.len(), header_line_tokens.len())
+                });
+                continue;
+            }
+            let plaza = self.name_normalizer.normalize_in(&line_token[0], &scope);
+            for (column, category) in categories.iter().enumerate().map(|(index, category)| (index + 1, *category)) {
+                let Ok(price) = Money::parse(&line_token[column], metadata.price_currency()) else {
+                    audit.error.push(PriceLoadError::InvalidPrice {
+                        file: file_name.to_string(),
+                        line: line_index + 1,
+                        column: column + 1,
+                        value: line_token[column].to_string()
+                    });
+                    continue;
+                };
+                let key = PriceKey::plaza(&plaza, category, metadata.network.clone());
+                let price = Price {
+                    price,
+                    year,
+                    file: file_name.to_string(),
+                    metadata: metadata.clone()
+                };
+                audit.add_loaded(category, &price.file);
+                insert_sorted(self.plaza_prices.entry(key).or_default(), price);
+            }
+        }
+        audit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::category::Category;
+    use crate::money::{Currency, Money};
+    use crate::test_prices::TestPrices;
+
+    #[test]
+    fn load_plaza_prices() {
+        let prices = TestPrices::new("plaza");
+        prices.write("plaza/2023_ATMB.tsv", "Plaza\tCar\t5\nChamonix\t2,50\t1,50\nSallanches\t3,00\n");
+        prices.write("plaza/2023_ATMB.meta.toml", "network = \"A40\"\n");
+        let price_service = prices.price_service();
+
+        let date = NaiveDate::from_ymd_opt(2023, 6, 1).unwrap();
+        let car = price_service.get_plaza_price("CHAMONIX", Category::Car, Some("A40"), date).unwrap();
+        assert_eq!(Money::new(250, Currency::EUR), car.price);
+        assert_eq!("2023_ATMB.tsv", car.file);
+        assert!(price_service.get_plaza_price("CHAMONIX", Category::Motorcycle, Some("A40"), date).is_some());
+        assert!(price_service.get_plaza_price("CHAMONIX", Category::Car, None, date).is_none());
+        // a plaza price is not a trip from the plaza to itself
+        assert!(price_service.station_files("CHAMONIX").is_empty());
+        assert_eq!(Some(3), price_service.load_audit().error.first().and_then(|error| error.line()));
+    }
+}
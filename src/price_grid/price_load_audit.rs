@@ -1,45 +1,212 @@
 use core::fmt;
+use std::collections::BTreeMap;
+use serde::{Serialize, Serializer};
+use chrono::NaiveDate;
+use serde::ser::SerializeStruct;
+use crate::category::Category;
+use crate::price::Price;
+use crate::price_grid::PriceKey;
 
-pub(crate) struct PriceLoadAudit {
-    pub(crate) loaded_cars: u32,
-    pub(crate) loaded_motorcycles: u32,
-    pub(crate) error: Vec<PriceLoadError>
+/// Count of the loaded prices per category and the errors met while loading the price files.
+#[derive(Serialize)]
+pub struct PriceLoadAudit {
+    pub loaded: BTreeMap<Category, u32>,
+    pub loaded_files: BTreeMap<String, u32>,
+    #[serde(rename = "errors")]
+    pub error: Vec<PriceLoadError>,
+    /// Different prices given by several files for the same key and validity date
+    pub conflicts: Vec<PriceConflict>,
+    /// Matrix prices contradicting a triangle declared symmetric
+    pub symmetry_conflicts: Vec<SymmetryConflict>
 }
 
 impl PriceLoadAudit {
     pub(crate) fn new() -> PriceLoadAudit {
         PriceLoadAudit {
-            loaded_cars: 0,
-            loaded_motorcycles: 0,
-            error: Vec::new()
+            loaded: BTreeMap::new(),
+            loaded_files: BTreeMap::new(),
+            error: Vec::new(),
+            conflicts: Vec::new(),
+            symmetry_conflicts: Vec::new()
         }
     }
 
+    pub(crate) fn add_loaded(&mut self, category: Category, file: &str) {
+        *self.loaded.entry(category).or_insert(0) += 1;
+        *self.loaded_files.entry(file.to_string()).or_insert(0) += 1;
+    }
+
     pub(crate) fn merge(&mut self, audit: &PriceLoadAudit) {
-        self.loaded_cars += audit.loaded_cars;
-        self.loaded_motorcycles += audit.loaded_motorcycles;
-        for error in &audit.error {
-            self.error.push(error.clone());
+        for (category, count) in &audit.loaded {
+            *self.loaded.entry(*category).or_insert(0) += count;
         }
+        for (file, count) in &audit.loaded_files {
+            *self.loaded_files.entry(file.to_string()).or_insert(0) += count;
+        }
+        self.error.extend(audit.error.iter().cloned());
+        self.conflicts.extend(audit.conflicts.iter().cloned());
+        self.symmetry_conflicts.extend(audit.symmetry_conflicts.iter().cloned());
     }
 }
 
 impl fmt::Display for PriceLoadAudit {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Loaded {} cars and {} motorcycles", self.loaded_cars, self.loaded_motorcycles)?;
-        if self.error.len() > 0 {
+        write!(f, "Loaded")?;
+        if self.loaded.is_empty() {
+            write!(f, " nothing")?;
+        }
+        for (category, count) in &self.loaded {
+            write!(f, " {} {}", count, category)?;
+        }
+        if !self.conflicts.is_empty() {
+            write!(f, "\n{} conflicting prices", self.conflicts.len())?;
+        }
+        if !self.symmetry_conflicts.is_empty() {
+            write!(f, "\n{} matrix prices contradicting a symmetric triangle", self.symmetry_conflicts.len())?;
+        }
+        if !self.error.is_empty() {
             write!(f, "\nErrors:")?;
             for error in &self.error {
-                write!(f, "\n\t{}:{}: {}", error.file_name, error.line, error.error)?;
+                write!(f, "\n\t{}", error)?;
             }
         }
         Ok(())
     }
 }
 
-#[derive(Clone)]
-pub(crate) struct PriceLoadError {
-    pub(crate) file_name: String,
-    pub(crate) line: String,
-    pub(crate) error: String
-}
\ No newline at end of file
+/// A price of a conflict and the file giving it.
+#[derive(Clone, Debug, Serialize)]
+pub struct ConflictPrice {
+    pub price: f64,
+    pub file: String
+}
+
+impl ConflictPrice {
+    pub(crate) fn new(price: &Price) -> ConflictPrice {
+        ConflictPrice {
+            price: price.price.to_f64(),
+            file: price.file.to_string()
+        }
+    }
+}
+
+/// Two files giving a different price for the same key and validity date, the precedence telling which one is used.
+#[derive(Clone, Debug, Serialize)]
+pub struct PriceConflict {
+    pub key: PriceKey,
+    pub effective_from: NaiveDate,
+    pub used: ConflictPrice,
+    pub ignored: ConflictPrice
+}
+
+impl fmt::Display for PriceConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} from {} : {:.2} in {} used over {:.2} in {}",
+               self.key, self.effective_from, self.used.price, self.used.file, self.ignored.price, self.ignored.file)
+    }
+}
+
+/// A matrix file giving another price than a triangle declared symmetric for the same trip and validity date.
+#[derive(Clone, Debug, Serialize)]
+pub struct SymmetryConflict {
+    pub key: PriceKey,
+    pub effective_from: NaiveDate,
+    pub matrix: ConflictPrice,
+    pub triangle: ConflictPrice
+}
+
+impl fmt::Display for SymmetryConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} from {} : {:.2} in matrix {} but {:.2} in symmetric triangle {}",
+               self.key, self.effective_from, self.matrix.price, self.matrix.file, self.triangle.price, self.triangle.file)
+    }
+}
+
+/// A price file, or a line of it, that could not be loaded. Lines and columns start at 1.
+#[derive(Clone, Debug)]
+pub enum PriceLoadError {
+    Io { file: String, cause: String },
+    InvalidFileName { file: String, cause: String },
+    InvalidMetadata { file: String, cause: String },
+    InvalidLine { file: String, line: usize, cause: String },
+    InvalidPrice { file: String, line: usize, column: usize, value: String }
+}
+
+impl PriceLoadError {
+    pub fn file(&self) -> &str {
+        match self {
+            PriceLoadError::Io { file, .. } => file,
+            PriceLoadError::InvalidFileName { file, .. } => file,
+            PriceLoadError::InvalidMetadata { file, .. } => file,
+            PriceLoadError::InvalidLine { file, .. } => file,
+            PriceLoadError::InvalidPrice { file, .. } => file
+        }
+    }
+
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            PriceLoadError::InvalidLine { line, .. } => Some(*line),
+            PriceLoadError::InvalidPrice { line, .. } => Some(*line),
+            _ => None
+        }
+    }
+
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            PriceLoadError::InvalidPrice { column, .. } => Some(*column),
+            _ => None
+        }
+    }
+
+    pub fn cause(&self) -> String {
+        match self {
+            PriceLoadError::Io { cause, .. } => cause.to_string(),
+            PriceLoadError::InvalidFileName { cause, .. } => format!("invalid file name, {}", cause),
+            PriceLoadError::InvalidMetadata { cause, .. } => format!("invalid metadata, {}", cause),
+            PriceLoadError::InvalidLine { cause, .. } => cause.to_string(),
+            PriceLoadError::InvalidPrice { value, .. } => format!("invalid price \"{}\"", value)
+        }
+    }
+}
+
+impl fmt::Display for PriceLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file())?;
+        if let Some(line) = self.line() {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(column) = self.column() {
+            write!(f, ":{}", column)?;
+        }
+        write!(f, ": {}", self.cause())
+    }
+}
+
+impl std::error::Error for PriceLoadError {}
+
+impl Serialize for PriceLoadError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut error = serializer.serialize_struct("PriceLoadError", 4)?;
+        error.serialize_field("file", self.file())?;
+        error.serialize_field("line", &self.line())?;
+        error.serialize_field("column", &self.column())?;
+        error.serialize_field("cause", &self.cause())?;
+        error.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PriceLoadError;
+
+    #[test]
+    fn display_location() {
+        let error = PriceLoadError::InvalidPrice {
+            file: "2023_ASF.tsv".to_string(),
+            line: 12,
+            column: 4,
+            value: "1,2,3".to_string()
+        };
+        assert_eq!("2023_ASF.tsv:12:4: invalid price \"1,2,3\"", error.to_string());
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs::read_to_string;
+use std::path::Path;
+use chrono::{Datelike, NaiveDate};
+use serde::Deserialize;
+use toml::value::Datetime;
+use crate::category::Category;
+use crate::money::Currency;
+
+/// Name of the manifest giving default metadata to every price file of a directory.
+const DIRECTORY_METADATA: &str = "meta.toml";
+/// Extension of the sidecar manifest of a price file, 2023_APRR.tsv -> 2023_APRR.meta.toml
+const FILE_METADATA_EXTENSION: &str = "meta.toml";
+
+/// Metadata of a price file, declared in its sidecar manifest.
+#[derive(Clone, Default, Debug)]
+pub struct PriceMetadata {
+    pub operator: Option<String>,
+    pub effective_from: Option<NaiveDate>,
+    pub effective_to: Option<NaiveDate>,
+    pub currency: Option<Currency>,
+    pub category: Option<Category>,
+    pub source_url: Option<String>,
+    /// The motorway or network of the stations of the file
+    pub network: Option<String>,
+    /// The trips priced by a triangle file
+    pub direction: Option<TriangleDirection>
+}
+
+/// The trips priced by a triangle file, whose line n holds the prices between the n previous stations and the station n.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriangleDirection {
+    /// The same price in both directions
+    Symmetric,
+    /// The trips from a station to the next ones
+    Forward,
+    /// The trips from a station to the previous ones
+    Backward
+}
+
+impl TriangleDirection {
+    pub fn from_name(name: &str) -> Option<TriangleDirection> {
+        match name.to_lowercase().as_str() {
+            "symmetric" => Some(TriangleDirection::Symmetric),
+            "forward" => Some(TriangleDirection::Forward),
+            "backward" => Some(TriangleDirection::Backward),
+            _ => None
+        }
+    }
+
+    /// The direction marked by a -forward or -backward suffix of the file name.
+    pub(crate) fn from_file_name(file_name: &str) -> Option<TriangleDirection> {
+        let stem = file_name.strip_suffix(".tsv").unwrap_or(file_name).to_lowercase();
+        if stem.ends_with("-forward") {
+            Some(TriangleDirection::Forward)
+        } else if stem.ends_with("-backward") {
+            Some(TriangleDirection::Backward)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MetadataFile {
+    operator: Option<String>,
+    effective_from: Option<Datetime>,
+    effective_to: Option<Datetime>,
+    currency: Option<String>,
+    category: Option<String>,
+    source_url: Option<String>,
+    network: Option<String>,
+    direction: Option<String>
+}
+
+impl PriceMetadata {
+    /// Load the metadata of a price file, the file manifest overriding the directory manifest.
+    pub(crate) fn load(path: &Path) -> Result<PriceMetadata, String> {
+        let mut metadata = PriceMetadata::default();
+        if let Some(directory) = path.parent() {
+            metadata.read_manifest(&directory.join(DIRECTORY_METADATA))?;
+        }
+        metadata.read_manifest(&path.with_extension(FILE_METADATA_EXTENSION))?;
+        Ok(metadata)
+    }
+
+    /// The currency of the prices, euro when not declared.
+    pub fn price_currency(&self) -> Currency {
+        self.currency.unwrap_or(Currency::EUR)
+    }
+
+    /// Year of the first day of validity, when declared.
+    pub fn year(&self) -> Option<u16> {
+        self.effective_from.map(|date| date.year() as u16)
+    }
+
+    fn read_manifest(&mut self, path: &Path) -> Result<(), String> {
+        if !path.is_file() {
+            return Ok(());
+        }
+        let content = read_to_string(path).map_err(|error| format!("Unable to read {} : {}", path.display(), error))?;
+        let manifest: MetadataFile = toml::from_str(&content).map_err(|error| format!("Invalid metadata {} : {}", path.display(), error))?;
+        if manifest.operator.is_some() {
+            self.operator = manifest.operator;
+        }
+        if let Some(date) = manifest.effective_from {
+            self.effective_from = Some(to_date(&date, path)?);
+        }
+        if let Some(date) = manifest.effective_to {
+            self.effective_to = Some(to_date(&date, path)?);
+        }
+        if let Some(currency) = manifest.currency {
+            self.currency = Some(Currency::new(&currency).ok_or(format!("Invalid currency {} in {}", currency, path.display()))?);
+        }
+        if let Some(category) = manifest.category {
+            self.category = Some(Category::from_name(&category).ok_or(format!("Invalid category {} in {}", category, path.display()))?);
+        }
+        if manifest.source_url.is_some() {
+            self.source_url = manifest.source_url;
+        }
+        if manifest.network.is_some() {
+            self.network = manifest.network;
+        }
+        if let Some(direction) = manifest.direction {
+            self.direction = Some(TriangleDirection::from_name(&direction).ok_or(format!("Invalid direction {} in {}", direction, path.display()))?);
+        }
+        Ok(())
+    }
+}
+
+fn to_date(datetime: &Datetime, path: &Path) -> Result<NaiveDate, String> {
+    datetime.date
+        .and_then(|date| NaiveDate::from_ymd_opt(date.year as i32, date.month as u32, date.day as u32))
+        .ok_or(format!("Invalid date {} in {}", datetime, path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::category::Category;
+    use super::{MetadataFile, PriceMetadata, TriangleDirection};
+
+    #[test]
+    fn parse_manifest() {
+        let path = std::env::temp_dir().join(format!("waze-toll-tool-metadata-test-{}.meta.toml", std::process::id()));
+        std::fs::write(&path, "operator = \"APRR\"\neffective_from = 2023-02-01\neffective_to = 2024-01-31\ncategory = \"2\"\n").unwrap();
+        let mut metadata = PriceMetadata::default();
+        metadata.read_manifest(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(Some("APRR".to_string()), metadata.operator);
+        assert_eq!(NaiveDate::from_ymd_opt(2023, 2, 1), metadata.effective_from);
+        assert_eq!(NaiveDate::from_ymd_opt(2024, 1, 31), metadata.effective_to);
+        assert_eq!(Some(Category::Intermediate), metadata.category);
+        assert_eq!(Some(2023), metadata.year());
+    }
+
+    #[test]
+    fn triangle_direction() {
+        assert_eq!(Some(TriangleDirection::Forward), TriangleDirection::from_file_name("2024_ALIS-A28-Forward.tsv"));
+        assert_eq!(Some(TriangleDirection::Backward), TriangleDirection::from_file_name("2024_ALIS-A28-backward.tsv"));
+        assert_eq!(None, TriangleDirection::from_file_name("2024_ALIS-A28.tsv"));
+        assert_eq!(Some(TriangleDirection::Symmetric), TriangleDirection::from_name("Symmetric"));
+    }
+
+    #[test]
+    fn reject_unknown_field() {
+        assert!(toml::from_str::<MetadataFile>("operateur = \"APRR\"").is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::read_to_string;
+use std::path::Path;
+use serde::Deserialize;
+use crate::price::Price;
+use crate::price_grid::price_load_audit::{ConflictPrice, PriceConflict, PriceLoadAudit};
+use crate::price_grid::PriceKey;
+
+/// Which price is used when several files give a different price for the same key and validity date.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum PricePrecedence {
+    /// The last file in name order
+    #[default]
+    FileName,
+    /// The file containing the first matching pattern of the list, then the last file in name order
+    FilePriority(Vec<String>),
+    /// The file having the fewest prices, then the last file in name order
+    MostSpecific
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrecedenceFile {
+    rule: String,
+    #[serde(default)]
+    files: Vec<String>
+}
+
+impl PricePrecedence {
+    /// Load the precedence rule, the file name order without precedence file.
+    ///
+    /// ```toml
+    /// rule = "file-priority" # or "most-specific" or "file-name"
+    /// files = ["2023_ASF_A7_A8_A9_A46_A54", "page2"]
+    /// ```
+    pub fn load(path: &Path) -> Result<PricePrecedence, String> {
+        if !path.is_file() {
+            return Ok(PricePrecedence::default());
+        }
+        let content = read_to_string(path).map_err(|error| format!("Unable to read {} : {}", path.display(), error))?;
+        let precedence: PrecedenceFile = toml::from_str(&content).map_err(|error| format!("Invalid precedence {} : {}", path.display(), error))?;
+        match precedence.rule.as_str() {
+            "file-name" => Ok(PricePrecedence::FileName),
+            "file-priority" => Ok(PricePrecedence::FilePriority(precedence.files)),
+            "most-specific" => Ok(PricePrecedence::MostSpecific),
+            rule => Err(format!("Unknown precedence rule {} in {}", rule, path.display()))
+        }
+    }
+
+    /// The rank of a file, the highest rank being used.
+    fn rank(&self, file: &str, loaded_files: &BTreeMap<String, u32>) -> (usize, Reverse<u32>) {
+        match self {
+            PricePrecedence::FileName => (0, Reverse(0)),
+            PricePrecedence::FilePriority(patterns) => {
+                let priority = patterns.iter()
+                    .position(|pattern| file.contains(pattern.as_str()))
+                    .map(|index| patterns.len() - index)
+                    .unwrap_or(0);
+                (priority, Reverse(0))
+            }
+            PricePrecedence::MostSpecific => (0, Reverse(loaded_files.get(file).copied().unwrap_or_default()))
+        }
+    }
+
+    /// Order the prices of each validity date so that the one to use is the last,
+    /// recording a conflict for each other file giving a different price.
+    /// Within a file, the last line is used.
+    pub(crate) fn resolve(&self, prices: &mut HashMap<PriceKey, Vec<Price>>, audit: &mut PriceLoadAudit) {
+        let mut conflicts = Vec::new();
+        for (key, history) in prices.iter_mut() {
+            let mut start = 0;
+            while start < history.len() {
+                let effective_from = history[start].effective_from();
+                let end = start + history[start..].partition_point(|price| price.effective_from() == effective_from);
+                let same_date = &mut history[start..end];
+                if same_date.len() > 1 {
+                    same_date.sort_by(|first, second| {
+                        (self.rank(&first.file, &audit.loaded_files), &first.file).cmp(&(self.rank(&second.file, &audit.loaded_files), &second.file))
+                    });
+                    let (used, ignored) = same_date.split_last().expect("several prices");
+                    for ignored in ignored.iter().filter(|ignored| ignored.file != used.file && ignored.price != used.price) {
+                        conflicts.push(PriceConflict {
+                            key: key.clone(),
+                            effective_from,
+                            used: ConflictPrice::new(used),
+                            ignored: ConflictPrice::new(ignored)
+                        });
+                    }
+                }
+                start = end;
+            }
+        }
+        conflicts.sort_by(|first, second| {
+            (&first.key.entry, &first.key.exit, first.key.category, &first.key.network, first.effective_from, &first.ignored.file)
+                .cmp(&(&second.key.entry, &second.key.exit, second.key.category, &second.key.network, second.effective_from, &second.ignored.file))
+        });
+        audit.conflicts.extend(conflicts);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::Arc;
+    use crate::category::Category;
+    use crate::money::{Currency, Money};
+    use crate::price::Price;
+    use crate::price_grid::price_load_audit::PriceLoadAudit;
+    use crate::price_grid::PriceKey;
+    use super::PricePrecedence;
+
+    fn price(price: i64, file: &str) -> Price {
+        Price {
+            price: Money::new(price, Currency::EUR),
+            year: 2023,
+            file: file.to_string(),
+            metadata: Arc::default()
+        }
+    }
+
+    fn resolve(precedence: PricePrecedence) -> (Price, PriceLoadAudit) {
+        let key = PriceKey {
+            entry: "A".to_string(),
+            exit: "B".to_string(),
+            category: Category::Car,
+            network: None
+        };
+        let mut prices = HashMap::from([(key.clone(), vec![price(120, "2023_A.tsv"), price(150, "2023_B.tsv"), price(120, "2023_C.tsv")])]);
+        let mut audit = PriceLoadAudit::new();
+        audit.loaded_files = BTreeMap::from([("2023_A.tsv".to_string(), 10), ("2023_B.tsv".to_string(), 2), ("2023_C.tsv".to_string(), 30)]);
+        precedence.resolve(&mut prices, &mut audit);
+        (prices[&key].last().unwrap().clone(), audit)
+    }
+
+    #[test]
+    fn resolve_conflicts() {
+        let (used, audit) = resolve(PricePrecedence::FileName);
+        assert_eq!("2023_C.tsv", used.file);
+        assert_eq!(1, audit.conflicts.len());
+        assert_eq!("2023_B.tsv", audit.conflicts[0].ignored.file);
+        let (used, audit) = resolve(PricePrecedence::MostSpecific);
+        assert_eq!("2023_B.tsv", used.file);
+        assert_eq!(2, audit.conflicts.len());
+        let (used, _) = resolve(PricePrecedence::FilePriority(vec!["_A".to_string(), "_B".to_string()]));
+        assert_eq!("2023_A.tsv", used.file);
+    }
+}
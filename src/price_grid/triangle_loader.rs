@@ -1,59 +1,147 @@
-use std::fs;
 use std::path::PathBuf;
 use crate::category::Category;
-use crate::io_tools::{is_dir, read_lines_tokens};
+use crate::io_tools::read_lines_tokens;
+use crate::money::Money;
+use crate::price::Price;
 use crate::price_grid::price_load_audit::{PriceLoadAudit, PriceLoadError};
-use crate::price_grid::{get_year, PriceLoader};
+use crate::price_grid::{file_name, get_year, io_error, price_files, PriceKey, PriceLoader, TriangleDirection};
 
 impl<'a> PriceLoader<'a> {
     pub(crate) fn load_triangles(&mut self, category: Category) -> PriceLoadAudit {
         println!("Loading triangle matrix {}", category);
-        let path = format!("prices/triangle/{}", category.to_string().to_lowercase());
+        let path = self.paths.triangle_category_dir(category);
         let mut audit = PriceLoadAudit::new();
-        if is_dir(&path) {
-            let paths = fs::read_dir(path).unwrap();
+        if path.is_dir() {
+            let paths = price_files(&path).unwrap_or_default();
             for path in paths {
-                let path = path.unwrap().path();
                 let triangle_audit = self.load_triangle(category, path);
                 audit.merge(&triangle_audit);
             }
         } else {
-            println!("Directory {} not found", path);
+            println!("Directory {} not found", path.display());
         }
         audit
     }
 
     fn load_triangle(&mut self, category: Category, path: PathBuf) -> PriceLoadAudit {
-        let file_name = path.clone();
-        let file_name = file_name.file_name().unwrap().to_str().unwrap();
-        let year = get_year(&file_name);
+        let file_name = file_name(&path);
         let mut audit = PriceLoadAudit::new();
-        println!("Loading triangle {} -> year {}", file_name, year);
-        if let Ok(tokenized_lines) = read_lines_tokens(path) {
-            let row_count = tokenized_lines.len();
-            for row in 0..row_count {
-                let line_token = &tokenized_lines[row];
-                let entry = self.name_normalizer.normalize(&line_token[line_token.len() - 1]);
-                for column in row + 1..row_count {
-                    let line_tokens_2 = &tokenized_lines[column];
-                    let exit = self.name_normalizer.normalize(&line_tokens_2[line_tokens_2.len() - 1]);
-                    let price_token = &line_tokens_2[row].replace(',', ".");
-                    if let Ok(value) = price_token.parse::<f32>() {
-                        let value = (value * 100.) as u16;
-                        self.insert_price(&mut audit, file_name, &entry, &exit, category, value, year);
-                        self.insert_price(&mut audit, file_name, &exit, &entry, category, value, year);
-                    } else {
-                        println!("Invalid price for {} -> {} : {}", entry, exit, price_token);
-                        let error = PriceLoadError {
-                            file_name: file_name.to_string(),
-                            line: "".to_string(),
-                            error: "Invalid line length".to_string()
-                        };
-                        audit.error.push(error);
+        let Some(metadata) = self.load_metadata(&mut audit, &path, &file_name) else {
+            return audit;
+        };
+        let year = metadata.year().unwrap_or_else(|| get_year(&file_name));
+        let category = metadata.category.unwrap_or(category);
+        // symmetry is only assumed when declared
+        let Some(direction) = TriangleDirection::from_file_name(&file_name).or(metadata.direction) else {
+            println!("Skipping {} : no direction", file_name);
+            audit.error.push(PriceLoadError::InvalidMetadata {
+                file: file_name.to_string(),
+                cause: "no direction, add a -forward or -backward suffix or declare the direction in the manifest".to_string()
+            });
+            return audit;
+        };
+        if direction == TriangleDirection::Symmetric {
+            self.symmetric_files.insert(file_name.to_string());
+        }
+        println!("Loading triangle {} -> year {} {:?}", file_name, year, direction);
+        let tokenized_lines = match read_lines_tokens(path) {
+            Ok(tokenized_lines) => tokenized_lines,
+            Err(error) => {
+                audit.error.push(io_error(&file_name, error));
+                return audit;
+            }
+        };
+        let scope = self.file_scope(&file_name, metadata.operator.as_deref(), metadata.network.as_deref());
+        // line n holds the prices from the n previous stations followed by the station name
+        for (line_index, line_tokens) in tokenized_lines.iter().enumerate() {
+            if line_tokens.len() != line_index + 1 {
+                audit.error.push(PriceLoadError::InvalidLine {
+                    file: file_name.to_string(),
+                    line: line_index + 1,
+                    cause: format!("{} columns instead of {}", line_tokens.len(), line_index + 1)
+                });
+            }
+        }
+        for (row, line_token) in tokenized_lines.iter().enumerate() {
+            let entry = self.name_normalizer.normalize_in(&line_token[line_token.len() - 1], &scope);
+            for (line_index, line_tokens_2) in tokenized_lines.iter().enumerate().skip(row + 1) {
+                if line_tokens_2.len() != line_index + 1 {
+                    continue;
+                }
+                let exit = self.name_normalizer.normalize_in(&line_tokens_2[line_tokens_2.len() - 1], &scope);
+                let price_token = &line_tokens_2[row];
+                if let Ok(value) = Money::parse(price_token, metadata.price_currency()) {
+                    let price = Price {
+                        price: value,
+                        year,
+                        file: file_name.to_string(),
+                        metadata: metadata.clone()
+                    };
+                    let network = metadata.network.clone();
+                    if direction != TriangleDirection::Backward {
+                        self.insert_price(&mut audit, PriceKey { entry: entry.to_string(), exit: exit.to_string(), category, network: network.clone() }, price.clone());
+                    }
+                    if direction != TriangleDirection::Forward {
+                        self.insert_price(&mut audit, PriceKey { entry: exit, exit: entry.to_string(), category, network }, price);
                     }
+                } else {
+                    println!("Invalid price for {} -> {} : {}", entry, exit, price_token);
+                    audit.error.push(PriceLoadError::InvalidPrice {
+                        file: file_name.to_string(),
+                        line: line_index + 1,
+                        column: row + 1,
+                        value: line_tokens_2[row].to_string()
+                    });
                 }
             }
         }
         audit
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::category::Category;
+    use crate::money::{Currency, Money};
+    use crate::price_grid::PriceKey;
+    use crate::test_prices::TestPrices;
+
+    fn key(entry: &str, exit: &str, category: Category) -> PriceKey {
+        PriceKey {
+            entry: entry.to_string(),
+            exit: exit.to_string(),
+            category,
+            network: None
+        }
+    }
+
+    #[test]
+    fn load_triangle_directions() {
+        let prices = TestPrices::new("triangle");
+        prices.write("triangle/car/2023_T-forward.tsv", "A\n1,00\tB\n2,00\t1,50\tC\n");
+        prices.write("triangle/car/2023_T-backward.tsv", "A\n1,10\tB\n2,20\t1,60\tC\n");
+        prices.write("triangle/car/2023_U.tsv", "A\n1,00\tB\n");
+        prices.write("triangle/motorcycle/meta.toml", "direction = \"symmetric\"\n");
+        prices.write("triangle/motorcycle/2023_T.tsv", "A\n0,60\tB\n");
+        prices.write("matrix/motorcycle/2023_M.tsv", "X\tA\tB\nA\t0\t0,70\nB\t0,60\t0\n");
+        let price_service = prices.price_service();
+
+        let date = NaiveDate::from_ymd_opt(2023, 6, 1).unwrap();
+        let price = |entry: &str, exit: &str, category: Category| price_service.get_price(&key(entry, exit, category), date).map(|price| (price.price, price.file.to_string()));
+        let euros = |minor: i64| Money::new(minor, Currency::EUR);
+        assert_eq!(Some((euros(150), "2023_T-forward.tsv".to_string())), price("B", "C", Category::Car));
+        assert_eq!(Some((euros(220), "2023_T-backward.tsv".to_string())), price("C", "A", Category::Car));
+        assert_eq!(Some((euros(100), "2023_T-forward.tsv".to_string())), price("A", "B", Category::Car));
+        // the triangle without direction is skipped
+        let audit = price_service.load_audit();
+        assert_eq!(vec!["2023_U.tsv"], audit.error.iter().map(|error| error.file()).collect::<Vec<&str>>());
+        assert!(!audit.loaded_files.contains_key("2023_U.tsv"));
+        // only the matrix price differing from the symmetric triangle is a contradiction
+        assert_eq!(1, audit.symmetry_conflicts.len());
+        let conflict = &audit.symmetry_conflicts[0];
+        assert_eq!(key("A", "B", Category::Motorcycle), conflict.key);
+        assert_eq!((0.7, "2023_M.tsv"), (conflict.matrix.price, conflict.matrix.file.as_str()));
+        assert_eq!((0.6, "2023_T.tsv"), (conflict.triangle.price, conflict.triangle.file.as_str()));
+    }
+}
@@ -0,0 +1,50 @@
+use std::path::{Path, PathBuf};
+use crate::category::Category;
+
+/// Location of the alias file and of the price grid directories.
+#[derive(Clone, Debug)]
+pub struct PricePaths {
+    pub alias_file: PathBuf,
+    pub flat_dir: PathBuf,
+    pub matrix_dir: PathBuf,
+    pub triangle_dir: PathBuf,
+    /// Fixed prices of the plazas of the open toll systems
+    pub plaza_dir: PathBuf,
+    /// Optional rule choosing between files giving different prices
+    pub precedence_file: PathBuf,
+    /// Optional rates converting the prices to the currency of the tolls
+    pub exchange_rate_file: PathBuf
+}
+
+impl PricePaths {
+    /// The standard layout below a root directory : alias.csv, flat, matrix, triangle, plaza, precedence.toml and exchange_rates.csv.
+    pub fn new<P: AsRef<Path>>(root: P) -> PricePaths {
+        let root = root.as_ref();
+        PricePaths {
+            alias_file: root.join("alias.csv"),
+            flat_dir: root.join("flat"),
+            matrix_dir: root.join("matrix"),
+            triangle_dir: root.join("triangle"),
+            plaza_dir: root.join("plaza"),
+            precedence_file: root.join("precedence.toml"),
+            exchange_rate_file: root.join("exchange_rates.csv")
+        }
+    }
+
+    /// Directory of the full matrix files of a category, matrix/car for cars.
+    pub fn matrix_category_dir(&self, category: Category) -> PathBuf {
+        self.matrix_dir.join(category.to_string().to_lowercase())
+    }
+
+    /// Directory of the triangle files of a category, triangle/car for cars.
+    pub fn triangle_category_dir(&self, category: Category) -> PathBuf {
+        self.triangle_dir.join(category.to_string().to_lowercase())
+    }
+}
+
+impl Default for PricePaths {
+    /// The prices directory of the current directory.
+    fn default() -> PricePaths {
+        PricePaths::new("prices")
+    }
+}
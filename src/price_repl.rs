@@ -0,0 +1,317 @@
+use chrono::NaiveDate;
+use enum_iterator::all;
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use crate::category::Category;
+use crate::name_normalizer::NameNormalizer;
+use crate::price::Price;
+use crate::price_grid::PriceKey;
+use crate::price_service::PriceService;
+
+/// The commands of the interactive mode, their arguments and what they give.
+const COMMANDS: [(&str, &str, &str); 8] = [
+    ("station", "<name>", "stations containing the name"),
+    ("price", "<entry> <exit> [category]", "prices of a trip"),
+    ("from", "<entry>", "prices of the trips from a station"),
+    ("to", "<exit>", "prices of the trips to a station"),
+    ("history", "<entry> <exit>", "every known price of a trip"),
+    ("files", "<station>", "price files giving prices for a station"),
+    ("help", "", "this help"),
+    ("quit", "", "leave the interactive mode")
+];
+
+/// Number of suggestions given for an unknown station.
+const SUGGESTION_COUNT: usize = 5;
+
+/// An interactive prompt answering questions about the stations and prices loaded once.
+/// Station names containing spaces are quoted when a command takes two of them : `price "TULLE NORD" "ST GERMAIN LES VERGNES"`.
+pub struct PriceRepl {
+    price_service: PriceService,
+    /// Every known station, sorted
+    stations: Vec<String>,
+    /// Date on which the prices apply
+    date: NaiveDate
+}
+
+/// Completes the commands and the normalized station names.
+struct StationCompleter<'a> {
+    name_normalizer: &'a NameNormalizer,
+    stations: &'a [String]
+}
+
+impl PriceRepl {
+    pub fn new(price_service: PriceService, date: NaiveDate) -> PriceRepl {
+        let stations = price_service.station_names().iter().cloned().collect();
+        PriceRepl {
+            price_service,
+            stations,
+            date
+        }
+    }
+
+    /// Answer the commands read on the terminal until quit, Ctrl-C or Ctrl-D.
+    pub fn run(&self) -> Result<(), String> {
+        let mut editor = Editor::<StationCompleter, DefaultHistory>::new().map_err(|error| format!("Unable to read the terminal : {}", error))?;
+        editor.set_helper(Some(StationCompleter {
+            name_normalizer: self.price_service.name_normalizer(),
+            stations: &self.stations
+        }));
+        println!("{} stations, prices on {}. Type help for the commands, tab to complete a station", self.stations.len(), self.date);
+        loop {
+            match editor.readline("> ") {
+                Ok(line) => {
+                    let line = line.trim();
+                    if line.is_empty() {
+                        continue;
+                    }
+                    let _ = editor.add_history_entry(line);
+                    if line == "quit" || line == "exit" {
+                        return Ok(());
+                    }
+                    match self.answer(line) {
+                        Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+                        Err(error) => println!("{}", error)
+                    }
+                }
+                Err(ReadlineError::Interrupted | ReadlineError::Eof) => return Ok(()),
+                Err(error) => return Err(format!("Unable to read the command : {}", error))
+            }
+        }
+    }
+
+    /// The lines answering a command.
+    pub fn answer(&self, line: &str) -> Result<Vec<String>, String> {
+        let args = split_args(line)?;
+        let Some((command, args)) = args.split_first() else {
+            return Ok(Vec::new());
+        };
+        match (command.as_str(), args.len()) {
+            ("station", 1..) => Ok(self.find_stations(&args.join(" "))),
+            ("price", 2 | 3) => self.prices(&args[0], &args[1], args.get(2)),
+            ("from", 1..) => {
+                let entry = self.station(&args.join(" "))?;
+                Ok(self.trip_prices(self.price_service.get_prices_from(&entry, self.date), &entry))
+            }
+            ("to", 1..) => {
+                let exit = self.station(&args.join(" "))?;
+                Ok(self.trip_prices(self.price_service.get_prices_to(&exit, self.date), &exit))
+            }
+            ("history", 2) => self.history(&args[0], &args[1]),
+            ("files", 1..) => self.files(&args.join(" ")),
+            ("help", 0) => Ok(COMMANDS.iter().map(|(command, args, help)| format!("{:<8}{:<27}{}", command, args, help)).collect()),
+            _ => Err(format!("Invalid command {}, type help for the commands", line))
+        }
+    }
+
+    /// The normalized name of a known station, or the closest stations.
+    fn station(&self, name: &str) -> Result<String, String> {
+        let station = self.price_service.name_normalizer().normalize(name);
+        if self.stations.binary_search(&station).is_ok() {
+            return Ok(station);
+        }
+        let suggestions = self.price_service.name_normalizer().suggest(name, &self.stations, SUGGESTION_COUNT);
+        if suggestions.is_empty() {
+            return Err(format!("Unknown station {}", station));
+        }
+        let names = suggestions.iter().map(|suggestion| suggestion.name.as_str()).collect::<Vec<_>>();
+        Err(format!("Unknown station {}, did you mean {} ?", station, names.join(", ")))
+    }
+
+    fn find_stations(&self, name: &str) -> Vec<String> {
+        let stations = self.price_service.find_stations(name);
+        if stations.is_empty() {
+            return match self.station(name) {
+                Ok(station) => vec![station],
+                Err(error) => vec![error]
+            };
+        }
+        stations.into_iter().cloned().collect()
+    }
+
+    fn prices(&self, entry: &str, exit: &str, category: Option<&String>) -> Result<Vec<String>, String> {
+        let (entry, exit) = (self.station(entry)?, self.station(exit)?);
+        let categories = match category {
+            Some(category) => vec![Category::from_name(category).ok_or_else(|| format!("Unknown category {}", category))?],
+            None => all::<Category>().collect()
+        };
+        let lines = categories.into_iter()
+            .flat_map(|category| self.price_service.trip_keys(&entry, &exit, category))
+            .filter_map(|key| self.price_service.get_price(key, self.date).map(|price| format!("{} : {}", key_label(key), price)))
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            return Ok(vec![format!("No price for {} -> {} on {}", entry, exit, self.date)]);
+        }
+        Ok(lines)
+    }
+
+    fn trip_prices(&self, prices: Vec<(&PriceKey, &Price)>, station: &str) -> Vec<String> {
+        if prices.is_empty() {
+            return vec![format!("No price for {} on {}", station, self.date)];
+        }
+        prices.into_iter()
+            .map(|(key, price)| format!("{} : {}", key, price))
+            .collect()
+    }
+
+    fn history(&self, entry: &str, exit: &str) -> Result<Vec<String>, String> {
+        let (entry, exit) = (self.station(entry)?, self.station(exit)?);
+        let lines = all::<Category>()
+            .flat_map(|category| self.price_service.trip_keys(&entry, &exit, category))
+            .flat_map(|key| {
+                self.price_service.get_price_history(key)
+                    .iter()
+                    .map(move |price| format!("{} from {} : {}", key_label(key), price.effective_from(), price))
+            })
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            return Ok(vec![format!("No price for {} -> {}", entry, exit)]);
+        }
+        Ok(lines)
+    }
+
+    fn files(&self, name: &str) -> Result<Vec<String>, String> {
+        let station = self.station(name)?;
+        Ok(self.price_service.station_files(&station)
+            .into_iter()
+            .map(|(file, count)| format!("{} : {} prices", file, count))
+            .collect())
+    }
+}
+
+/// The category of a trip price, followed by its network if any.
+fn key_label(key: &PriceKey) -> String {
+    match &key.network {
+        Some(network) => format!("{} on {}", key.category, network),
+        None => key.category.to_string()
+    }
+}
+
+/// Split a command line on spaces, except between double quotes.
+fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                arg.get_or_insert_with(String::new);
+            }
+            c if c.is_whitespace() && !quoted => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c)
+        }
+    }
+    if quoted {
+        return Err(format!("Missing closing quote in {}", line));
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+impl StationCompleter<'_> {
+    /// The start of the argument before the cursor and its completions : the commands for the first argument, else the stations.
+    fn complete_line(&self, line: &str) -> (usize, Vec<Pair>) {
+        let mut start = 0;
+        let mut quoted = false;
+        for (index, c) in line.char_indices() {
+            if c == '"' {
+                if !quoted {
+                    start = index;
+                }
+                quoted = !quoted;
+            } else if c.is_whitespace() && !quoted {
+                start = index + 1;
+            }
+        }
+        let typed = &line[start..];
+        if line[..start].trim().is_empty() {
+            let commands = COMMANDS.iter()
+                .filter(|(command, _, _)| command.starts_with(typed))
+                .map(|(command, _, _)| Pair {
+                    display: command.to_string(),
+                    replacement: format!("{} ", command)
+                })
+                .collect();
+            return (start, commands);
+        }
+        let mut prefix = self.name_normalizer.normalize(typed.trim_start_matches('"'));
+        if typed.len() > 1 && typed.ends_with(' ') {
+            prefix.push(' ');
+        }
+        let stations = self.stations.iter()
+            .filter(|station| station.starts_with(&prefix))
+            .map(|station| Pair {
+                display: station.to_string(),
+                replacement: if station.contains(' ') { format!("\"{}\" ", station) } else { format!("{} ", station) }
+            })
+            .collect();
+        (start, stations)
+    }
+}
+
+impl Completer for StationCompleter<'_> {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        Ok(self.complete_line(&line[..pos]))
+    }
+}
+
+impl Hinter for StationCompleter<'_> {
+    type Hint = String;
+}
+
+impl Highlighter for StationCompleter<'_> {}
+
+impl Validator for StationCompleter<'_> {}
+
+impl Helper for StationCompleter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::test_prices::TestPrices;
+    use super::{split_args, PriceRepl, StationCompleter};
+
+    #[test]
+    fn answer_commands() {
+        assert_eq!(Ok(vec!["price".to_string(), "TULLE NORD".to_string(), "".to_string()]), split_args("price \"TULLE NORD\"  \"\""));
+        assert!(split_args("price \"TULLE").is_err());
+
+        let prices = TestPrices::new("repl");
+        prices.write("triangle/car/meta.toml", "direction = \"symmetric\"\n");
+        prices.write("triangle/car/2023_TEST.tsv", "TULLE NORD\n1,40\tTULLE SUD\n2,10\t0,70\tUSSEL\n");
+        prices.write("triangle/car/2023_A89.tsv", "USSEL\n0,90\tTULLE SUD\n");
+        prices.write("triangle/car/2023_A89.meta.toml", "network = \"A89\"\n");
+        let repl = PriceRepl::new(prices.price_service(), NaiveDate::from_ymd_opt(2023, 6, 1).unwrap());
+
+        assert_eq!(Ok(vec!["TULLE NORD".to_string(), "TULLE SUD".to_string()]), repl.answer("station tulle"));
+        assert_eq!(Ok(vec!["Car : 2023 1.40 EUR 2023_TEST.tsv".to_string()]), repl.answer("price \"tulle nord\" \"Tulle Sud\" car"));
+        assert_eq!(Ok(vec!["Car TULLE SUD->TULLE NORD : 2023 1.40 EUR 2023_TEST.tsv".to_string(), "Car USSEL->TULLE NORD : 2023 2.10 EUR 2023_TEST.tsv".to_string()]),
+                   repl.answer("to tulle nord"));
+        assert_eq!(Ok(vec!["Car from 2023-01-01 : 2023 0.70 EUR 2023_TEST.tsv".to_string(), "Car on A89 from 2023-01-01 : 2023 0.90 EUR 2023_A89.tsv".to_string()]),
+                   repl.answer("history ussel \"TULLE SUD\""));
+        assert_eq!(Ok(vec!["Car : 2023 0.70 EUR 2023_TEST.tsv".to_string(), "Car on A89 : 2023 0.90 EUR 2023_A89.tsv".to_string()]), repl.answer("price \"TULLE SUD\" ussel"));
+        assert_eq!(Ok(vec!["2023_A89.tsv : 2 prices".to_string(), "2023_TEST.tsv : 4 prices".to_string()]), repl.answer("files ussel"));
+        assert_eq!(Err("Unknown station TULE SUD, did you mean TULLE SUD, TULLE NORD ?".to_string()), repl.answer("from tule sud"));
+        assert!(repl.answer("prices").is_err());
+
+        let completer = StationCompleter {
+            name_normalizer: repl.price_service.name_normalizer(),
+            stations: &repl.stations
+        };
+        let replacements = |line: &str| {
+            let (start, pairs) = completer.complete_line(line);
+            (start, pairs.into_iter().map(|pair| pair.replacement).collect::<Vec<_>>())
+        };
+        assert_eq!((0, vec!["history ".to_string()]), replacements("hi"));
+        assert_eq!((6, vec!["\"TULLE NORD\" ".to_string(), "\"TULLE SUD\" ".to_string()]), replacements("price tul"));
+        assert_eq!((19, vec!["\"TULLE SUD\" ".to_string()]), replacements("price \"TULLE NORD\" \"tulle s"));
+        assert_eq!((6, vec!["USSEL ".to_string()]), replacements("files us"));
+    }
+}
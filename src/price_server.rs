@@ -0,0 +1,392 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use chrono::{NaiveDate, Utc};
+use enum_iterator::all;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+use crate::category::Category;
+use crate::price_grid::PriceKey;
+use crate::price_paths::PricePaths;
+use crate::price_service::{MatrixOptions, MissingPricePolicy, PriceService};
+use crate::toll_file::TollFile;
+
+/// How often the price files are checked for changes while no request comes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The state of every file below the price paths.
+type FilesState = BTreeMap<PathBuf, FileState>;
+
+/// Size, modification time and content hash of a file, the content being hashed again only when the size or time changed.
+#[derive(Clone)]
+struct FileState {
+    len: u64,
+    modified: Option<SystemTime>,
+    hash: u64
+}
+
+/// An http server answering json price lookups, reloading the prices when their files change.
+///
+/// - `GET /price?entry=<name>&exit=<name>[&category=<category>][&date=<yyyy-mm-dd>][&network=<network>]`
+/// - `GET /stations?q=<name>`
+/// - `POST /build-matrix[?date=<yyyy-mm-dd>][&infer=true][&missing=<policy>]` with a toll file as body
+/// - `GET /status`
+pub struct PriceServer {
+    server: Server,
+    paths: PricePaths,
+    price_service: PriceService,
+    files_state: FilesState,
+    loaded_at: SystemTime,
+    reloads: u32
+}
+
+/// A request error, answered with its status code and message.
+struct HttpError {
+    status: u16,
+    message: String
+}
+
+impl HttpError {
+    fn bad_request(message: String) -> HttpError {
+        HttpError {
+            status: 400,
+            message
+        }
+    }
+
+    fn not_found(message: String) -> HttpError {
+        HttpError {
+            status: 404,
+            message
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FoundPrice<'a> {
+    key: PriceKey,
+    price: f64,
+    currency: String,
+    effective_from: NaiveDate,
+    file: &'a str
+}
+
+impl PriceServer {
+    /// Load the prices and listen on the address, 127.0.0.1:8080 for instance.
+    pub fn new(paths: PricePaths, address: &str) -> Result<PriceServer, String> {
+        let files_state = files_state(&paths, &FilesState::new());
+        let price_service = PriceService::new(&paths).map_err(|error| format!("Unable to load the prices : {}", error))?;
+        let server = Server::http(address).map_err(|error| format!("Unable to listen on {} : {}", address, error))?;
+        Ok(PriceServer {
+            server,
+            paths,
+            price_service,
+            files_state,
+            loaded_at: SystemTime::now(),
+            reloads: 0
+        })
+    }
+
+    /// The address the server listens on.
+    pub fn address(&self) -> String {
+        self.server.server_addr().to_string()
+    }
+
+    /// Answer the requests until the process ends.
+    pub fn run(&mut self) {
+        println!("Listening on http://{}", self.address());
+        loop {
+            match self.server.recv_timeout(RELOAD_INTERVAL) {
+                Ok(Some(request)) => {
+                    self.reload_if_changed();
+                    self.answer(request);
+                }
+                Ok(None) => self.reload_if_changed(),
+                Err(error) => println!("Unable to receive a request : {}", error)
+            }
+        }
+    }
+
+    /// Reload the prices when a price file was added, removed or changed, keeping the current prices on failure.
+    fn reload_if_changed(&mut self) {
+        let files_state = files_state(&self.paths, &self.files_state);
+        let changed = !files_state.iter().map(|(path, state)| (path, state.hash)).eq(self.files_state.iter().map(|(path, state)| (path, state.hash)));
+        self.files_state = files_state;
+        if !changed {
+            return;
+        }
+        println!("Price files changed, reloading");
+        match PriceService::new(&self.paths) {
+            Ok(price_service) => {
+                self.price_service = price_service;
+                self.loaded_at = SystemTime::now();
+                self.reloads += 1;
+            }
+            Err(error) => println!("Unable to reload the prices, keeping the previous ones : {}", error)
+        }
+    }
+
+    fn answer(&self, mut request: Request) {
+        let url = request.url().to_string();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let params = query_params(query);
+        println!("{} {}", request.method(), url);
+        let result = match (request.method(), path) {
+            (Method::Get, "/price") => self.price(&params),
+            (Method::Get, "/stations") => self.stations(&params),
+            (Method::Post, "/build-matrix") => {
+                let mut body = String::new();
+                match request.as_reader().read_to_string(&mut body) {
+                    Ok(_) => self.build_matrix(&params, &body),
+                    Err(error) => Err(HttpError::bad_request(format!("Unable to read the body : {}", error)))
+                }
+            }
+            (Method::Get, "/status") => Ok(self.status()),
+            _ => Err(HttpError::not_found(format!("Unknown endpoint {} {}", request.method(), path)))
+        };
+        let (status, body) = match result {
+            Ok(body) => (200, body),
+            Err(error) => (error.status, json!({ "error": error.message }))
+        };
+        let content_type = Header::from_bytes("Content-Type", "application/json").expect("valid header");
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(content_type);
+        if let Err(error) = request.respond(response) {
+            println!("Unable to answer {} : {}", url, error);
+        }
+    }
+
+    /// The prices of a trip on a date, for a category or for every category.
+    fn price(&self, params: &HashMap<String, String>) -> Result<Value, HttpError> {
+        let entry = required_param(params, "entry")?;
+        let exit = required_param(params, "exit")?;
+        let date = date_param(params)?;
+        let categories = match params.get("category") {
+            Some(category) => vec![Category::from_name(category).ok_or_else(|| HttpError::bad_request(format!("Invalid category {}", category)))?],
+            None => all::<Category>().collect()
+        };
+        let name_normalizer = self.price_service.name_normalizer();
+        let (entry_name, exit_name) = (name_normalizer.normalize(entry), name_normalizer.normalize(exit));
+        let mut prices = Vec::new();
+        for category in categories {
+            // without network, the trip is looked up on every network
+            let keys = match params.get("network") {
+                Some(network) => vec![PriceKey {
+                    entry: entry_name.to_string(),
+                    exit: exit_name.to_string(),
+                    category,
+                    network: Some(network.to_uppercase())
+                }],
+                None => self.price_service.trip_keys(&entry_name, &exit_name, category).into_iter().cloned().collect()
+            };
+            for key in keys {
+                if let Some(price) = self.price_service.get_price(&key, date) {
+                    prices.push(FoundPrice {
+                        key,
+                        price: price.price.to_f64(),
+                        currency: price.price.currency().to_string(),
+                        effective_from: price.effective_from(),
+                        file: &price.file
+                    });
+                }
+            }
+        }
+        if prices.is_empty() {
+            return Err(HttpError::not_found(format!("No price for {} -> {} on {}", entry, exit, date)));
+        }
+        Ok(json!({ "date": date, "prices": prices }))
+    }
+
+    /// The stations containing a name, or the closest ones when none does.
+    fn stations(&self, params: &HashMap<String, String>) -> Result<Value, HttpError> {
+        let name = required_param(params, "q")?;
+        let stations = self.price_service.find_stations(name);
+        let suggestions = if stations.is_empty() { self.price_service.suggest_stations(name, 5) } else { Vec::new() };
+        Ok(json!({ "stations": stations, "suggestions": suggestions }))
+    }
+
+    /// The toll file of the body with its matrices rebuilt, and the audit of each toll.
+    fn build_matrix(&self, params: &HashMap<String, String>, body: &str) -> Result<Value, HttpError> {
+        let mut toll_file: TollFile = serde_json::from_str(body).map_err(|error| HttpError::bad_request(format!("Invalid toll file : {}", error)))?;
+        let mut options = MatrixOptions::new(date_param(params)?);
+        options.infer_missing = params.get("infer").is_some_and(|infer| infer == "true");
+        if let Some(policy) = params.get("missing") {
+            options.missing_price = MissingPricePolicy::from_name(policy).ok_or_else(|| HttpError::bad_request(format!("Invalid missing price policy {}", policy)))?;
+        }
+        let audits = self.price_service.update_toll_file(&mut toll_file, &options);
+        Ok(json!({ "toll_file": toll_file, "tolls": audits }))
+    }
+
+    fn status(&self) -> Value {
+        let loaded_at = chrono::DateTime::<Utc>::from(self.loaded_at);
+        json!({
+            "loaded_at": loaded_at.to_rfc3339(),
+            "reloads": self.reloads,
+            "stations": self.price_service.station_names().len(),
+            "load": self.price_service.load_audit()
+        })
+    }
+}
+
+fn required_param<'a>(params: &'a HashMap<String, String>, name: &str) -> Result<&'a String, HttpError> {
+    params.get(name).ok_or_else(|| HttpError::bad_request(format!("Missing parameter {}", name)))
+}
+
+/// The date parameter, today by default.
+fn date_param(params: &HashMap<String, String>) -> Result<NaiveDate, HttpError> {
+    match params.get("date") {
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|error| HttpError::bad_request(format!("Invalid date {} : {}", date, error))),
+        None => Ok(Utc::now().date_naive())
+    }
+}
+
+/// The decoded parameters of a query string.
+fn query_params(query: &str) -> HashMap<String, String> {
+    query.split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            (decode(name), decode(value))
+        })
+        .collect()
+}
+
+/// Decode the + and %XX escapes of a query string part.
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let byte = text.get(index + 1..index + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match byte {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        index += 2;
+                    }
+                    None => decoded.push(b'%')
+                }
+            }
+            byte => decoded.push(byte)
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// The state of the alias, precedence and exchange rate files and of every file below the price directories,
+/// reusing the hash of the previous state for the files whose size and modification time did not change.
+fn files_state(paths: &PricePaths, previous: &FilesState) -> FilesState {
+    let mut state = FilesState::new();
+    for file in [&paths.alias_file, &paths.precedence_file, &paths.exchange_rate_file] {
+        add_file_state(&mut state, previous, file);
+    }
+    for directory in [&paths.flat_dir, &paths.matrix_dir, &paths.triangle_dir, &paths.plaza_dir] {
+        add_directory_state(&mut state, previous, directory);
+    }
+    state
+}
+
+fn add_directory_state(state: &mut FilesState, previous: &FilesState, directory: &Path) {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return;
+    };
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        if path.is_dir() {
+            add_directory_state(state, previous, &path);
+        } else {
+            add_file_state(state, previous, &path);
+        }
+    }
+}
+
+fn add_file_state(state: &mut FilesState, previous: &FilesState, file: &Path) {
+    let Ok(metadata) = fs::metadata(file) else {
+        return;
+    };
+    let (len, modified) = (metadata.len(), metadata.modified().ok());
+    if let Some(file_state) = previous.get(file).filter(|file_state| file_state.len == len && file_state.modified == modified) {
+        state.insert(file.to_path_buf(), file_state.clone());
+        return;
+    }
+    if let Ok(content) = fs::read(file) {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        state.insert(file.to_path_buf(), FileState {
+            len,
+            modified,
+            hash: hasher.finish()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+    use std::thread;
+    use serde_json::Value;
+    use crate::test_prices::TestPrices;
+    use super::{decode, PriceServer};
+
+    fn request(address: &str, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", method, path, address, body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn serve_prices() {
+        assert_eq!("ST GERMAIN", decode("ST+GERMAIN"));
+        assert_eq!("A->B é", decode("A-%3EB%20%C3%A9"));
+
+        let prices = TestPrices::new("server");
+        prices.write("triangle/car/meta.toml", "direction = \"symmetric\"\n");
+        let price_file = prices.write("triangle/car/2023_TEST.tsv", "TULLE NORD\n1,40\tST GERMAIN LES VERGNES\n");
+        prices.write("triangle/car/2023_A89.tsv", "TULLE NORD\n1,60\tST GERMAIN LES VERGNES\n");
+        prices.write("triangle/car/2023_A89.meta.toml", "network = \"A89\"\n");
+
+        let mut server = PriceServer::new(prices.paths(), "127.0.0.1:0").unwrap();
+        let address = server.address();
+        thread::spawn(move || server.run());
+
+        let (status, body) = request(&address, "GET", "/price?entry=tulle+nord&exit=ST%20GERMAIN%20LES%20VERGNES&category=car&date=2023-06-01", "");
+        assert_eq!(200, status);
+        assert_eq!(1.4, body["prices"][0]["price"]);
+        assert_eq!("2023_TEST.tsv", body["prices"][0]["file"]);
+        assert_eq!(1.6, body["prices"][1]["price"]);
+        assert_eq!("A89", body["prices"][1]["key"]["network"]);
+        let (_, body) = request(&address, "GET", "/price?entry=TULLE+NORD&exit=ST+GERMAIN+LES+VERGNES&date=2023-06-01&network=a89", "");
+        assert_eq!(1, body["prices"].as_array().unwrap().len());
+        assert_eq!(1.6, body["prices"][0]["price"]);
+        let (status, _) = request(&address, "GET", "/price?entry=TULLE+NORD&exit=NOWHERE", "");
+        assert_eq!(404, status);
+        let (_, body) = request(&address, "GET", "/stations?q=tulle", "");
+        assert_eq!("TULLE NORD", body["stations"][0]);
+
+        let toll_file = r#"{"tolls": [{"toll_id": "T", "road_local_name": "", "currency": "€", "currency_code": "EUR", "polyline": "", "type": "TOLL",
+            "rules": ["entry_exit_price"], "entry_exit_matrix": [], "sections": [
+            {"section_id": "TULLE NORD", "road_local_name": "", "section_local_name": "", "location": [1.77, 45.29], "segments": []},
+            {"section_id": "ST GERMAIN LES VERGNES", "road_local_name": "", "section_local_name": "", "location": [1.58, 45.27], "segments": []}]}]}"#;
+        let (status, body) = request(&address, "POST", "/build-matrix?date=2023-06-01", toll_file);
+        assert_eq!(200, status);
+        assert_eq!(1.4, body["toll_file"]["tolls"][0]["entry_exit_matrix"][0]["matrix_prices"][0][1]);
+
+        // another size, seen whatever the precision of the modification time
+        std::fs::write(&price_file, "TULLE NORD\n1,55\tST GERMAIN LES VERGNES\n").unwrap();
+        let (_, body) = request(&address, "GET", "/price?entry=TULLE+NORD&exit=ST+GERMAIN+LES+VERGNES&category=Car&date=2023-06-01", "");
+        assert_eq!(1.55, body["prices"][0]["price"]);
+        let (_, body) = request(&address, "GET", "/status", "");
+        assert_eq!(1, body["reloads"]);
+    }
+}
@@ -1,185 +1,734 @@
-use std::collections::HashMap;
-use std::{fmt, fs};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
 use std::fmt::Formatter;
-use chrono::{Datelike, Utc};
+use std::path::Path;
+use chrono::NaiveDate;
+use enum_iterator::all;
 use crate::category::Category;
-use crate::name_normalizer::NameNormalizer;
-use crate::price::Price;
-use crate::price_grid::{PriceKey, PriceLoader};
-use crate::toll_file::{load_toll_file, Matrix, Section, Toll, TollFile};
+use crate::exchange_rate::ExchangeRates;
+use crate::matrix_audit::{CurrencyMismatch, DerivedPrice, MatrixAudit, TollAudit, UnknownSection};
+use crate::matrix_diff::{CellDiff, TollDiff};
+use crate::money::{Currency, Money};
+use crate::name_normalizer::{NameNormalizer, NameScope, Suggestion};
+use crate::price::{price_on, Price};
+use crate::price_consistency::{asymmetric_prices, detours, distance_km, per_km_outliers, yearly_changes, ConsistencyIssue, ConsistencyOptions, TollTrip};
+use crate::price_evolution::{price_evolution, PriceEvolution};
+use crate::price_grid::{PriceKey, PriceLoadAudit, PriceLoadError, PriceLoader};
+use crate::price_paths::PricePaths;
+use crate::toll_file::{backup_toll_file, load_toll_document, load_toll_file, write_toll_document, FixedPrice, Matrix, Toll, TollFile};
 
-struct Audit {
-    obsolete: u16,
-    found: u16,
-    not_found: u16,
-    obsolete_files: Vec<String>
+/// What to write in the matrix when a price is unknown.
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub enum MissingPricePolicy {
+    /// Leave the toll unchanged
+    Fail,
+    /// Keep the price of the input matrix of the same vehicles, leaving the toll unchanged when there is none
+    #[default]
+    KeepExisting,
+    /// Write the given value so that the missing prices can be spotted
+    Sentinel(f64),
+    /// Write 0, as for a free trip
+    Zero
 }
 
-impl Audit {
-    fn new() -> Audit {
-        Audit {
-            obsolete: 0,
-            found: 0,
-            not_found: 0,
-            obsolete_files: Vec::new()
+impl MissingPricePolicy {
+    /// Parse fail, keep, zero or a sentinel value.
+    pub fn from_name(name: &str) -> Option<MissingPricePolicy> {
+        match name.to_lowercase().as_str() {
+            "fail" => Some(MissingPricePolicy::Fail),
+            "keep" => Some(MissingPricePolicy::KeepExisting),
+            "zero" => Some(MissingPricePolicy::Zero),
+            value => value.parse::<f64>().ok().map(MissingPricePolicy::Sentinel)
         }
     }
+}
+
+/// How the matrices of the tolls are built.
+#[derive(Clone, Debug)]
+pub struct MatrixOptions {
+    /// Date on which the prices apply
+    pub date: NaiveDate,
+    /// Fill the missing prices with the cheapest sum of two known prices through another section of the same toll
+    pub infer_missing: bool,
+    /// What to write for the prices still missing
+    pub missing_price: MissingPricePolicy
+}
 
-    fn add_obsolete_file(&mut self, file: &String) {
-        if !self.obsolete_files.contains(file) {
-            self.obsolete_files.push(file.clone());
+impl MatrixOptions {
+    pub fn new(date: NaiveDate) -> MatrixOptions {
+        MatrixOptions {
+            date,
+            infer_missing: false,
+            missing_price: MissingPricePolicy::default()
         }
     }
 }
 
-pub(crate) struct PriceService {
-    prices: HashMap<PriceKey, Price>,
-    name_normalizer: NameNormalizer
+/// Number of suggestions given for an unknown station.
+const SUGGESTION_COUNT: usize = 5;
+
+/// The price file of each cell of a matrix, None for an unknown price.
+type PriceSources = Vec<Vec<Option<String>>>;
+
+/// What becomes of the matrix of a category once built.
+enum MatrixOutcome<'a> {
+    /// Write the built matrix
+    Write(Matrix),
+    /// Keep the input matrix, no price being found
+    Keep(&'a Matrix),
+    /// No price found and no input matrix, the toll gets no matrix for the category
+    Skip,
+    /// Leave the toll unchanged, for the given reason
+    Fail(String)
+}
+
+/// The prices of every price grid, indexed by trip.
+pub struct PriceService {
+    prices: HashMap<PriceKey, Vec<Price>>,
+    plaza_prices: HashMap<PriceKey, Vec<Price>>,
+    /// The name scope of every price file, whose aliases also apply to the sections of the tolls of its motorways
+    file_scopes: Vec<NameScope>,
+    /// Every entry, exit and plaza name
+    station_names: BTreeSet<String>,
+    /// The sorted keys of every trip, by entry and exit
+    trips: HashMap<(String, String), Vec<PriceKey>>,
+    name_normalizer: NameNormalizer,
+    load_audit: PriceLoadAudit,
+    exchange_rates: ExchangeRates
 }
 
 impl PriceService {
-    pub(crate) fn new() -> PriceService {
-        let name_normalizer = NameNormalizer::new();
+    /// Load the aliases and every price grid found in the given paths.
+    /// Invalid price files are skipped and reported in the load audit, only an unreadable alias file is an error.
+    pub fn new(paths: &PricePaths) -> Result<PriceService, PriceLoadError> {
+        let name_normalizer = NameNormalizer::new(&paths.alias_file)?;
         let mut price_loader = PriceLoader {
+            paths,
             name_normalizer: &name_normalizer,
-            prices: HashMap::new()
+            prices: HashMap::new(),
+            plaza_prices: HashMap::new(),
+            symmetric_files: HashSet::new(),
+            matrix_files: HashSet::new(),
+            file_scopes: Vec::new()
         };
-        let audit = price_loader.load_prices();
-        println!("Price loader audit : {}", audit);
+        let mut load_audit = price_loader.load_prices();
+        load_audit.error.splice(0..0, name_normalizer.errors().iter().cloned());
+        let exchange_rates = ExchangeRates::load(&paths.exchange_rate_file).unwrap_or_else(|cause| {
+            println!("{}, not converting any price", cause);
+            load_audit.error.push(PriceLoadError::InvalidMetadata {
+                file: paths.exchange_rate_file.display().to_string(),
+                cause
+            });
+            ExchangeRates::default()
+        });
+        println!("Price loader audit : {}", load_audit);
         let prices = price_loader.prices;
-        PriceService {
-            prices,
-            name_normalizer
+        let plaza_prices = price_loader.plaza_prices;
+        let file_scopes = price_loader.file_scopes;
+        let station_names = prices.keys()
+            .chain(plaza_prices.keys())
+            .flat_map(|key| [&key.entry, &key.exit])
+            .cloned()
+            .collect();
+        let mut trips: HashMap<(String, String), Vec<PriceKey>> = HashMap::new();
+        for key in prices.keys() {
+            trips.entry((key.entry.to_string(), key.exit.to_string())).or_default().push(key.clone());
         }
+        trips.values_mut().for_each(|keys| keys.sort());
+        Ok(PriceService {
+            prices,
+            plaza_prices,
+            file_scopes,
+            station_names,
+            trips,
+            name_normalizer,
+            load_audit,
+            exchange_rates
+        })
     }
 
-    pub(crate) fn get_prices(&self, entry_name: &String) {
-        println!("Getting prices for {}", entry_name);
-        let mut found_prices = false;
-        for (key, value) in &self.prices {
-            let key_entry_name = &key.entry;
-            if key_entry_name.contains(entry_name) {
-                found_prices = true;
-                let destination = &key.exit;
-                println!("{} {}", destination, value.price);
+    /// The price applying on the given date, of the network of the key or else of a key without network.
+    pub fn get_price(&self, key: &PriceKey, date: NaiveDate) -> Option<&Price> {
+        price_on(self.get_price_history(key), date)
+    }
+
+    /// The fixed price of a passage at a normalized plaza name, of the network or else without network.
+    pub fn get_plaza_price(&self, plaza: &str, category: Category, network: Option<&str>, date: NaiveDate) -> Option<&Price> {
+        let key = PriceKey::plaza(plaza, category, network.map(str::to_string));
+        let history = self.plaza_prices.get(&key).or_else(|| {
+            key.network.as_ref()?;
+            self.plaza_prices.get(&PriceKey {
+                network: None,
+                ..key.clone()
+            })
+        });
+        price_on(history?, date)
+    }
+
+    /// Every known price of a key, oldest first, of the network of the key or else of a key without network.
+    pub fn get_price_history(&self, key: &PriceKey) -> &[Price] {
+        let history = self.prices.get(key).or_else(|| {
+            key.network.as_ref()?;
+            self.prices.get(&PriceKey {
+                network: None,
+                ..key.clone()
+            })
+        });
+        history.map(|history| history.as_slice()).unwrap_or_default()
+    }
+
+    /// The keys of the trip between two normalized stations for a category, on every network, the key without network first.
+    pub fn trip_keys(&self, entry: &str, exit: &str, category: Category) -> Vec<&PriceKey> {
+        self.trips.get(&(entry.to_string(), exit.to_string()))
+            .map(|keys| keys.iter().filter(|key| key.category == category).collect())
+            .unwrap_or_default()
+    }
+
+    /// Prices applying on the given date of the trips whose entry contains the given name.
+    pub fn get_prices(&self, entry_name: &str, date: NaiveDate) -> Vec<(&PriceKey, &Price)> {
+        self.prices.iter()
+            .filter(|(key, _)| key.entry.contains(entry_name))
+            .filter_map(|(key, history)| price_on(history, date).map(|price| (key, price)))
+            .collect()
+    }
+
+    /// Prices applying on the given date of the trips from a normalized station, sorted by key.
+    pub fn get_prices_from(&self, entry: &str, date: NaiveDate) -> Vec<(&PriceKey, &Price)> {
+        self.get_trip_prices(date, |key| key.entry == entry)
+    }
+
+    /// Prices applying on the given date of the trips to a normalized station, sorted by key.
+    pub fn get_prices_to(&self, exit: &str, date: NaiveDate) -> Vec<(&PriceKey, &Price)> {
+        self.get_trip_prices(date, |key| key.exit == exit)
+    }
+
+    fn get_trip_prices(&self, date: NaiveDate, filter: impl Fn(&PriceKey) -> bool) -> Vec<(&PriceKey, &Price)> {
+        let mut prices = self.prices.iter()
+            .filter(|(key, _)| filter(key))
+            .filter_map(|(key, history)| price_on(history, date).map(|price| (key, price)))
+            .collect::<Vec<_>>();
+        prices.sort_by_key(|(key, _)| *key);
+        prices
+    }
+
+    /// Number of prices given by each file for the trips from or to a normalized station.
+    pub fn station_files(&self, station: &str) -> BTreeMap<&str, usize> {
+        let mut files = BTreeMap::new();
+        for (key, history) in &self.prices {
+            if key.entry == station || key.exit == station {
+                for price in history {
+                    *files.entry(price.file.as_str()).or_default() += 1;
+                }
             }
         }
-        if !found_prices {
-            println!("No prices found for {}", entry_name);
-        }
+        files
     }
 
-    pub(crate) fn get_station(&self, name: &String) {
-        println!("Getting station for {}", name);
+    /// Normalized station names containing the normalized given name.
+    pub fn find_stations(&self, name: &str) -> Vec<&String> {
         let name = self.name_normalizer.normalize(name);
         let mut found_stations: Vec<&String> = Vec::new();
-        for (key, _value) in &self.prices {
+        for key in self.prices.keys() {
             let key_entry_name = &key.entry;
-            if key_entry_name.contains(&name) {
-                if !found_stations.contains(&key_entry_name) {
-                    found_stations.push(key_entry_name);
-                }
+            if key_entry_name.contains(&name) && !found_stations.contains(&key_entry_name) {
+                found_stations.push(key_entry_name);
             }
         }
-        if found_stations.is_empty() {
-            println!("No station found for {}", name);
+        found_stations.sort();
+        found_stations
+    }
+
+    /// Every entry, exit and plaza name of the price grids.
+    pub fn station_names(&self) -> &BTreeSet<String> {
+        &self.station_names
+    }
+
+    /// The known stations closest to a name, best first.
+    pub fn suggest_stations(&self, name: &str, limit: usize) -> Vec<Suggestion> {
+        self.name_normalizer.suggest(name, self.station_names(), limit)
+    }
+
+    /// The normalized names of the sections of a toll, using the aliases of their motorways
+    /// and of the price files and operators of these motorways.
+    fn section_ids(&self, toll: &Toll) -> Vec<String> {
+        toll.sections.iter()
+            .map(|section| {
+                let mut scope = NameScope::for_motorways(&[&section.road_local_name, &toll.road_local_name]);
+                for file_scope in &self.file_scopes {
+                    scope.add_price_file(file_scope);
+                }
+                self.name_normalizer.normalize_in(&section.section_id, &scope)
+            })
+            .collect()
+    }
+
+    pub fn name_normalizer(&self) -> &NameNormalizer {
+        &self.name_normalizer
+    }
+
+    pub fn load_audit(&self) -> &PriceLoadAudit {
+        &self.load_audit
+    }
+
+    /// Look for the prices which are probably typos : asymmetric prices, detours cheaper than the direct trip,
+    /// changes from the previous year above the threshold and, using the section locations of the given tolls,
+    /// prices per km far from the median of their toll.
+    pub fn check_consistency(&self, tolls: &[&Toll], options: &ConsistencyOptions) -> Vec<ConsistencyIssue> {
+        let mut issues = asymmetric_prices(&self.prices, options.date);
+        issues.extend(detours(&self.prices, options.date, options.detour_ratio));
+        issues.extend(yearly_changes(&self.prices, options.max_change));
+        issues.sort_by(|first, second| issue_key(first).cmp(&issue_key(second)));
+        for toll in tolls {
+            let trips = self.toll_trips(toll, options.date);
+            issues.extend(per_km_outliers(&toll.toll_id, &trips, options.outlier_ratio));
+        }
+        issues
+    }
+
+    /// The change of the prices of each file family and category between its consecutive years.
+    pub fn price_evolution(&self) -> Vec<PriceEvolution> {
+        price_evolution(&self.prices)
+    }
+
+    /// The priced trips between the sections of a toll, with their distance as the crow flies.
+    fn toll_trips(&self, toll: &Toll, date: NaiveDate) -> Vec<TollTrip> {
+        let section_ids = self.section_ids(toll);
+        let mut trips = Vec::new();
+        for category in all::<Category>() {
+            for (entry_index, entry) in section_ids.iter().enumerate() {
+                for (exit_index, exit) in section_ids.iter().enumerate().filter(|(_, exit)| *exit != entry) {
+                    let key = PriceKey {
+                        entry: entry.to_string(),
+                        exit: exit.to_string(),
+                        category,
+                        network: toll_network(toll)
+                    };
+                    if let Some(price) = self.get_price(&key, date) {
+                        trips.push(TollTrip {
+                            key,
+                            price: price.price.to_f64(),
+                            distance: distance_km(toll.sections[entry_index].location, toll.sections[exit_index].location)
+                        });
+                    }
+                }
+            }
         }
-        for station in found_stations {
-            println!("{}", station);
+        trips
+    }
+
+    /// Update the matrices of a toll file and write them to the output file, if any.
+    /// The toll file is backed up first when it is also the output file.
+    pub fn build_matrix(&self, toll_file_name: &Path, output_file_name: Option<&Path>, options: &MatrixOptions) -> Result<Vec<TollAudit>, String> {
+        println!("Building matrix for {} on {}", toll_file_name.display(), options.date);
+        let mut document = load_toll_document(toll_file_name)
+            .map_err(|error| format!("Failed to load toll file {}\n{}", toll_file_name.display(), error))?;
+        println!("Loaded toll file {} containing {} toll", toll_file_name.display(), document.toll_file.tolls.len());
+        let audits = self.update_toll_file(&mut document.toll_file, options);
+        let Some(output_file_name) = output_file_name else {
+            return Ok(audits);
+        };
+        if output_file_name == toll_file_name {
+            backup_toll_file(toll_file_name)
+                .map_err(|error| format!("Failed to back up {}\n{}", toll_file_name.display(), error))?;
         }
+        write_toll_document(&document, output_file_name)
+            .map_err(|error| format!("Failed to write {}\n{}", output_file_name.display(), error))?;
+        Ok(audits)
     }
 
-    pub(crate) fn build_matrix(&self, toll_file_name: &String) {
-        println!("Building matrix for {}", toll_file_name);
-        let toll_file = load_toll_file(toll_file_name);
-        if toll_file.is_ok() {
-            let mut toll_file = toll_file.unwrap();
-            println!("Loaded toll file {} containing {} toll", toll_file_name, toll_file.tolls.len());
-            for toll in &mut toll_file.tolls {
-                self.update_toll_matrix(toll)
+    /// The cells of the entry/exit matrices of a toll file which would change when building them.
+    pub fn diff_matrix(&self, toll_file_name: &Path, options: &MatrixOptions) -> Result<Vec<TollDiff>, String> {
+        println!("Comparing matrix of {} with the prices of {}", toll_file_name.display(), options.date);
+        let toll_file = load_toll_file(toll_file_name)
+            .map_err(|error| format!("Failed to load toll file {}\n{}", toll_file_name.display(), error))?;
+        Ok(toll_file.tolls.iter()
+            .filter(|toll| toll.pricing().is_ok_and(|pricing| pricing.has_entry_exit()))
+            .map(|toll| match toll_currency(toll) {
+                Ok(currency) => self.diff_toll(toll, currency, options),
+                Err(cause) => TollDiff {
+                    toll_id: toll.toll_id.to_string(),
+                    changes: Vec::new(),
+                    errors: vec![format!("Toll skipped : {}", cause)],
+                    currency_mismatches: Vec::new()
+                }
+            })
+            .collect())
+    }
+
+    fn diff_toll(&self, toll: &Toll, currency: Currency, options: &MatrixOptions) -> TollDiff {
+        let section_ids = self.section_ids(toll);
+        let mut changes = Vec::new();
+        let mut errors = Vec::new();
+        let mut currency_mismatches = Vec::new();
+        for category in all::<Category>() {
+            let existing = existing_matrix(toll, category);
+            let (matrix, mut audit, sources) = self.build_matrix_category(toll, &section_ids, category, currency, existing, options);
+            currency_mismatches.append(&mut audit.currency_mismatches);
+            let matrix = match matrix_outcome(category, matrix, &audit, existing, options) {
+                MatrixOutcome::Write(matrix) => matrix,
+                // the matrix would not be rebuilt
+                MatrixOutcome::Keep(_) | MatrixOutcome::Skip => continue,
+                MatrixOutcome::Fail(cause) => {
+                    errors.push(format!("Toll left unchanged because of {}", cause));
+                    continue;
+                }
+            };
+            for (entry_index, row) in matrix.matrix_prices.iter().enumerate() {
+                for (exit_index, new_price) in row.iter().enumerate() {
+                    if section_ids[entry_index] == section_ids[exit_index] {
+                        continue;
+                    }
+                    let key = PriceKey {
+                        entry: section_ids[entry_index].to_string(),
+                        exit: section_ids[exit_index].to_string(),
+                        category,
+                        network: toll_network(toll)
+                    };
+                    let old_price = existing.map(|matrix| matrix.matrix_prices[entry_index][exit_index]);
+                    if let Some(change) = CellDiff::new(key, old_price, *new_price, sources[entry_index][exit_index].clone()) {
+                        changes.push(change);
+                    }
+                }
             }
-            write_toll_file(&toll_file, "out.json");
-        } else {
-            eprintln!("Failed to load toll file {}", toll_file_name);
-            eprintln!("{}", toll_file.err().unwrap());
         }
+        if !currency_mismatches.is_empty() {
+            errors.push(format!("Toll left unchanged because of {} prices without exchange rate to {}", currency_mismatches.len(), currency));
+        }
+        TollDiff {
+            toll_id: toll.toll_id.to_string(),
+            changes,
+            errors,
+            currency_mismatches
+        }
+    }
+
+    /// Replace the entry/exit matrices of every toll.
+    pub fn update_toll_file(&self, toll_file: &mut TollFile, options: &MatrixOptions) -> Vec<TollAudit> {
+        toll_file.tolls.iter_mut()
+            .map(|toll| self.update_toll(toll, options))
+            .collect()
     }
 
-    fn update_toll_matrix(&self, toll: &mut Toll) {
-        println!("Updating toll matrix for {}", toll.toll_id);
-        if toll.rules.len() != 1 && toll.rules.get(0).unwrap() != "entry_exit_price" {
-            println!("Skipping toll {} because it has {} rules", toll.toll_id, toll.rules.len());
-            return;
+    fn update_toll(&self, toll: &mut Toll, options: &MatrixOptions) -> TollAudit {
+        println!("Updating toll {}", toll.toll_id);
+        let mut toll_audit = TollAudit::new(&toll.toll_id, toll.sections.len());
+        let pricing = match toll.pricing() {
+            Ok(pricing) => pricing,
+            Err(cause) => {
+                println!("Skipping toll {} : {}", toll.toll_id, cause);
+                toll_audit.skipped = true;
+                return toll_audit;
+            }
+        };
+        let currency = match toll_currency(toll) {
+            Ok(currency) => currency,
+            Err(cause) => {
+                println!("Skipping toll {} : {}", toll.toll_id, cause);
+                toll_audit.skipped = true;
+                return toll_audit;
+            }
+        };
+        let station_names = self.station_names();
+        let section_ids = self.section_ids(toll);
+        for (section, normalized) in toll.sections.iter().zip(&section_ids) {
+            if station_names.contains(normalized) {
+                continue;
+            }
+            let suggestions = self.name_normalizer.suggest(&section.section_id, station_names, SUGGESTION_COUNT);
+            println!("Unknown station {}, suggestions : {}",
+                     normalized,
+                     suggestions.iter().map(|suggestion| format!("{} ({:.2})", suggestion.name, suggestion.score)).collect::<Vec<String>>().join(", "));
+            toll_audit.unknown_sections.push(UnknownSection {
+                section_id: section.section_id.to_string(),
+                normalized: normalized.to_string(),
+                suggestions
+            });
+        }
+        let matrixes = if pricing.has_entry_exit() { self.update_entry_exit_matrix(toll, &section_ids, currency, &mut toll_audit, options) } else { Vec::new() };
+        let fixed_prices = if pricing.has_fixed() { self.update_fixed_price(toll, &section_ids, currency, &mut toll_audit, options) } else { Vec::new() };
+        let currency_mismatches = toll_audit.categories.values()
+            .chain(toll_audit.fixed_prices.values())
+            .map(|audit| audit.currency_mismatches.len())
+            .sum::<usize>();
+        if currency_mismatches > 0 {
+            println!("Leaving toll {} unchanged because of {} prices without exchange rate to {}", toll.toll_id, currency_mismatches, currency);
+            toll_audit.failed = true;
+        }
+        if !toll_audit.failed {
+            toll.entry_exit_matrix = matrixes;
+            toll.fixed_price = fixed_prices;
         }
-        let (car_matrix, car_audit) = self.build_matrix_category(&toll.sections, Category::Car);
-        let (motorcycle_matrix, motorcycle_audit) = self.build_matrix_category(&toll.sections, Category::Motorcycle);
-        toll.entry_exit_matrix = vec![car_matrix, motorcycle_matrix];
-        println!("Car        : Found {} prices, {} obsolete, {} not found",
-                 car_audit.found,
-                 car_audit.obsolete,
-                 car_audit.not_found);
-        println!("Motocycles : Found {} prices, {} obsolete, {} not found",
-                 motorcycle_audit.found,
-                 motorcycle_audit.obsolete,
-                 motorcycle_audit.not_found);
+        toll_audit
+    }
 
-        for obsolete_file in car_audit.obsolete_files {
-            println!("Obsolete file : {}", obsolete_file);
+    fn update_entry_exit_matrix(&self, toll: &Toll, section_ids: &[String], currency: Currency, toll_audit: &mut TollAudit, options: &MatrixOptions) -> Vec<Matrix> {
+        let mut matrixes = Vec::new();
+        for category in all::<Category>() {
+            let existing = existing_matrix(toll, category);
+            let (matrix, audit, _) = self.build_matrix_category(toll, section_ids, category, currency, existing, options);
+            println!("{:<15}: Found {} prices, {} obsolete, {} derived, {} converted, {} not found, {} kept",
+                     category,
+                     audit.found,
+                     audit.obsolete,
+                     audit.derived.len(),
+                     audit.converted,
+                     audit.not_found,
+                     audit.kept);
+            for obsolete_file in &audit.obsolete_files {
+                println!("Obsolete file : {}", obsolete_file);
+            }
+            let outcome = matrix_outcome(category, matrix, &audit, existing, options);
+            toll_audit.categories.insert(category, audit);
+            match outcome {
+                MatrixOutcome::Write(matrix) => matrixes.push(matrix),
+                MatrixOutcome::Keep(existing) => {
+                    println!("No price found for {}, keeping its existing matrix", category);
+                    matrixes.push(existing.clone());
+                }
+                MatrixOutcome::Skip => {
+                    println!("No price found for {}, skipping its matrix", category);
+                    toll_audit.skipped_categories.push(category);
+                }
+                MatrixOutcome::Fail(cause) => {
+                    println!("Leaving toll {} unchanged because of {}", toll.toll_id, cause);
+                    toll_audit.failed = true;
+                }
+            }
         }
-        for obsolete_file in motorcycle_audit.obsolete_files {
-            println!("Obsolete file : {}", obsolete_file);
+        matrixes
+    }
+
+    /// The price of the first section of the toll having a plaza price, for every category.
+    /// The fixed prices of other vehicles are kept as they were.
+    fn update_fixed_price(&self, toll: &Toll, plazas: &[String], currency: Currency, toll_audit: &mut TollAudit, options: &MatrixOptions) -> Vec<FixedPrice> {
+        let mut fixed_prices = Vec::new();
+        let network = toll_network(toll);
+        for category in all::<Category>() {
+            let existing = toll.fixed_price.iter().find(|fixed_price| is_category_fixed_price(fixed_price, category));
+            let new_fixed_price = |price: f64| FixedPrice {
+                friendly_name: category.to_string(),
+                price,
+                permit_id: existing.map(|fixed_price| fixed_price.permit_id.to_string()).unwrap_or_default(),
+                limit_to_vehicles: category.limit_to_vehicles(),
+                extra: existing.map(|fixed_price| fixed_price.extra.clone()).unwrap_or_default()
+            };
+            let mut audit = MatrixAudit::default();
+            let plaza_price = plazas.iter()
+                .find_map(|plaza| self.get_plaza_price(plaza, category, network.as_deref(), options.date).map(|price| (plaza, price)));
+            if let Some((plaza, price)) = plaza_price {
+                let key = PriceKey::plaza(plaza, category, network.clone());
+                // without exchange rate the currency mismatch leaves the toll unchanged
+                if let Some(converted) = self.convert_price(&key, price, currency, &mut audit) {
+                    audit.add_found(&key, price, options.date);
+                    fixed_prices.push(new_fixed_price(converted.to_f64()));
+                }
+            } else {
+                println!("No plaza price found for {} at {}", category, plazas.join(", "));
+                audit.not_found += 1;
+                audit.missing.extend(plazas.iter().map(|plaza| PriceKey::plaza(plaza, category, network.clone())));
+                match (existing, options.missing_price) {
+                    (None, _) => {}
+                    (Some(existing), MissingPricePolicy::KeepExisting) => {
+                        audit.kept += 1;
+                        fixed_prices.push(existing.clone());
+                    }
+                    (Some(_), MissingPricePolicy::Fail) => {
+                        println!("Leaving toll {} unchanged because of no known {} plaza price", toll.toll_id, category);
+                        toll_audit.failed = true;
+                    }
+                    (Some(_), MissingPricePolicy::Sentinel(value)) => fixed_prices.push(new_fixed_price(value)),
+                    (Some(_), MissingPricePolicy::Zero) => fixed_prices.push(new_fixed_price(0.0))
+                }
+            }
+            toll_audit.fixed_prices.insert(category, audit);
         }
+        fixed_prices.extend(toll.fixed_price.iter()
+            .filter(|fixed_price| all::<Category>().all(|category| !is_category_fixed_price(fixed_price, category)))
+            .cloned());
+        fixed_prices
     }
 
-    fn build_matrix_category(&self, sections: &Vec<Section>, category: Category) -> (Matrix, Audit) {
-        let year = Utc::now().year() as u16;
-        let limit_to_vehicles = match category {
-            Category::Car => vec!["PRIVATE".to_string(), "TAXI".to_string(), "EV".to_string()],
-            Category::Motorcycle => vec!["MOTORCYCLE".to_string()],
+    /// A price in the currency of a toll, recording the conversion or the missing exchange rate in the audit.
+    fn convert_price(&self, key: &PriceKey, price: &Price, currency: Currency, audit: &mut MatrixAudit) -> Option<Money> {
+        let Some(converted) = self.exchange_rates.convert(price.price, currency) else {
+            println!("No exchange rate from {} to {} for {} of {}", price.price.currency(), currency, key, price.file);
+            audit.currency_mismatches.push(CurrencyMismatch {
+                key: key.clone(),
+                currency: price.price.currency(),
+                file: price.file.to_string()
+            });
+            return None;
         };
+        if converted.currency() != price.price.currency() {
+            audit.converted += 1;
+        }
+        Some(converted)
+    }
+
+    /// The matrix of a category in the currency of the toll, with the file of each of its prices.
+    fn build_matrix_category(&self, toll: &Toll, section_ids: &[String], category: Category, currency: Currency, existing: Option<&Matrix>, options: &MatrixOptions) -> (Matrix, MatrixAudit, PriceSources) {
+        let date = options.date;
+        let network = toll_network(toll);
+        let mut audit = MatrixAudit::default();
+        let mut known_prices: Vec<Vec<Option<(&Price, Money)>>> = Vec::new();
+        for entry_id in section_ids {
+            let mut row = Vec::new();
+            for exit_id in section_ids {
+                let key = PriceKey {
+                    entry: entry_id.to_string(),
+                    exit: exit_id.to_string(),
+                    category,
+                    network: network.clone()
+                };
+                let price = self.get_price(&key, date)
+                    .filter(|_| entry_id != exit_id)
+                    .and_then(|price| Some((price, self.convert_price(&key, price, currency, &mut audit)?)));
+                row.push(price);
+            }
+            known_prices.push(row);
+        }
+        let converted_prices = known_prices.iter()
+            .map(|row| row.iter().map(|price| price.map(|(_, converted)| converted)).collect())
+            .collect::<Vec<Vec<Option<Money>>>>();
 
         let mut matrix_prices: Vec<Vec<f64>> = Vec::new();
-        let mut audit = Audit::new();
-        for entry_section in sections {
+        let mut sources: PriceSources = Vec::new();
+        for (entry_index, entry_id) in section_ids.iter().enumerate() {
             let mut row = Vec::new();
-            let entry_id = self.name_normalizer.normalize(&entry_section.section_id);
-            for exit_section in sections {
-                let exit_id = self.name_normalizer.normalize(&exit_section.section_id);
+            let mut row_sources = Vec::new();
+            for (exit_index, exit_id) in section_ids.iter().enumerate() {
                 if entry_id == exit_id {
                     row.push(0.0);
+                    row_sources.push(None);
+                    continue;
+                }
+                let key = PriceKey {
+                    entry: entry_id.to_string(),
+                    exit: exit_id.to_string(),
+                    category,
+                    network: network.clone()
+                };
+                if let Some((price, converted)) = known_prices[entry_index][exit_index] {
+                    row.push(converted.to_f64());
+                    row_sources.push(Some(price.file.to_string()));
+                    audit.add_found(&key, price, date);
+                } else if let Some((via_index, price)) = options.infer_missing.then(|| infer_price(&converted_prices, section_ids, entry_index, exit_index)).flatten() {
+                    let price = price.to_f64();
+                    let via = section_ids[via_index].to_string();
+                    println!("Derived price {} for {} via {}", price, key, via);
+                    row.push(price);
+                    row_sources.push(Some(format!("derived via {}", via)));
+                    audit.derived.push(DerivedPrice {
+                        key,
+                        via,
+                        price
+                    });
                 } else {
-                    let key = PriceKey {
-                        entry: entry_id.to_string(),
-                        exit: exit_id.to_string(),
-                        category
-                    };
-                    let price = self.prices.get(&key);
-                    if price.is_none() {
-                        println!("Unknown price for {}", key);
-                        row.push(0.0);
-                        audit.not_found += 1;
-                    } else {
-                        let price = price.unwrap();
-                        row.push(price.price as f64 / 100f64);
-                        if year > price.year {
-                            println!("Price is obsolete (from {}) for {}", price.year, key);
-                            audit.obsolete += 1;
-                            audit.add_obsolete_file(&price.file);
+                    let value = match options.missing_price {
+                        MissingPricePolicy::KeepExisting => {
+                            let existing_value = existing.and_then(|matrix| matrix.matrix_prices.get(entry_index)?.get(exit_index).copied());
+                            if existing_value.is_some() {
+                                audit.kept += 1;
+                            } else {
+                                audit.not_kept += 1;
+                            }
+                            existing_value
                         }
-                        audit.found += 1;
+                        MissingPricePolicy::Sentinel(value) => Some(value),
+                        MissingPricePolicy::Fail | MissingPricePolicy::Zero => Some(0.0)
+                    };
+                    match value {
+                        Some(value) => println!("Unknown price for {}, writing {}", key, value),
+                        None => println!("Unknown price for {} without input price to keep", key)
                     }
+                    // a cell without value leaves the toll unchanged, so its 0 is never written
+                    let value = value.unwrap_or(0.0);
+                    row.push(value);
+                    row_sources.push(None);
+                    audit.not_found += 1;
+                    audit.missing.push(key);
                 }
             }
             matrix_prices.push(row);
+            sources.push(row_sources);
         }
         let matrix = Matrix {
             friendly_name: category.to_string(),
             matrix_prices,
-            permit_id: "".to_string(),
-            limit_to_vehicles
+            permit_id: existing.map(|matrix| matrix.permit_id.to_string()).unwrap_or_default(),
+            limit_to_vehicles: category.limit_to_vehicles(),
+            extra: existing.map(|matrix| matrix.extra.clone()).unwrap_or_default()
+        };
+        (matrix, audit, sources)
+    }
+}
+
+/// The currency of the prices of a toll, euro when not given.
+fn toll_currency(toll: &Toll) -> Result<Currency, String> {
+    if toll.currency_code.trim().is_empty() {
+        return Ok(Currency::EUR);
+    }
+    Currency::new(&toll.currency_code).ok_or(format!("Invalid currency code {}", toll.currency_code))
+}
+
+/// The network of the prices of a toll, its motorway.
+fn toll_network(toll: &Toll) -> Option<String> {
+    Some(toll.road_local_name.trim().to_uppercase()).filter(|network| !network.is_empty())
+}
+
+/// The order of the issues not related to a toll : by kind, then by trip.
+fn issue_key(issue: &ConsistencyIssue) -> (u8, &PriceKey) {
+    match issue {
+        ConsistencyIssue::Asymmetric { key, .. } => (0, key),
+        ConsistencyIssue::Detour { key, .. } => (1, key),
+        ConsistencyIssue::PerKmOutlier { key, .. } => (2, key),
+        ConsistencyIssue::YearlyChange { key, .. } => (3, key)
+    }
+}
+
+/// The input matrix of the vehicles of a category, when it matches the sections of the toll.
+fn existing_matrix(toll: &Toll, category: Category) -> Option<&Matrix> {
+    let limit_to_vehicles = category.limit_to_vehicles();
+    toll.entry_exit_matrix.iter()
+        .find(|matrix| matrix.limit_to_vehicles == limit_to_vehicles || matrix.friendly_name.eq_ignore_ascii_case(&category.to_string()))
+        .filter(|matrix| matrix.matrix_prices.len() == toll.sections.len()
+            && matrix.matrix_prices.iter().all(|row| row.len() == toll.sections.len()))
+}
+
+/// Whether a fixed price applies to the vehicles of a category.
+fn is_category_fixed_price(fixed_price: &FixedPrice, category: Category) -> bool {
+    fixed_price.limit_to_vehicles == category.limit_to_vehicles() || fixed_price.friendly_name.eq_ignore_ascii_case(&category.to_string())
+}
+
+/// Write the built matrix of a category unless no price was found, or unless the missing prices leave the toll unchanged.
+fn matrix_outcome<'a>(category: Category, matrix: Matrix, audit: &MatrixAudit, existing: Option<&'a Matrix>, options: &MatrixOptions) -> MatrixOutcome<'a> {
+    if audit.found == 0 {
+        return match (existing, options.missing_price) {
+            (None, _) => MatrixOutcome::Skip,
+            (Some(existing), MissingPricePolicy::KeepExisting) => MatrixOutcome::Keep(existing),
+            (Some(_), MissingPricePolicy::Fail) => MatrixOutcome::Fail(format!("no known {} price", category)),
+            (Some(_), MissingPricePolicy::Sentinel(_) | MissingPricePolicy::Zero) => MatrixOutcome::Write(matrix)
         };
-        (matrix, audit)
     }
+    if audit.not_found > 0 && options.missing_price == MissingPricePolicy::Fail {
+        return MatrixOutcome::Fail(format!("{} unknown {} prices", audit.not_found, category));
+    }
+    if audit.not_kept > 0 {
+        return MatrixOutcome::Fail(format!("{} unknown {} prices without input price to keep (--missing <value> writes them)", audit.not_kept, category));
+    }
+    MatrixOutcome::Write(matrix)
+}
+
+/// The cheapest sum of two known prices going through another section of the toll, and this section.
+fn infer_price(known_prices: &[Vec<Option<Money>>], section_ids: &[String], entry_index: usize, exit_index: usize) -> Option<(usize, Money)> {
+    (0..section_ids.len())
+        .filter(|via_index| section_ids[*via_index] != section_ids[entry_index] && section_ids[*via_index] != section_ids[exit_index])
+        .filter_map(|via_index| {
+            let first = known_prices[entry_index][via_index]?;
+            let second = known_prices[via_index][exit_index]?;
+            Some((via_index, first.checked_add(second)?))
+        })
+        .min_by_key(|(_, price)| price.minor())
 }
 
 impl fmt::Display for PriceService {
@@ -188,8 +737,187 @@ impl fmt::Display for PriceService {
     }
 }
 
-fn write_toll_file(toll: &TollFile, file_name: &str) {
-    let json = serde_json::to_string_pretty(&toll).unwrap();
-    fs::write(file_name, json).expect("Unable to write file");
-    println!("Wrote {}", file_name);
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use crate::category::Category;
+    use crate::money::{Currency, Money};
+    use crate::test_prices::TestPrices;
+    use crate::toll_file::Toll;
+    use super::{infer_price, MatrixOptions, MissingPricePolicy, PriceService};
+
+    /// A price service knowing only the car price of A -> B and B -> A, and the car price of the plaza A.
+    fn price_service(test: &str) -> PriceService {
+        let prices = TestPrices::new(test);
+        prices.write("triangle/car/meta.toml", "direction = \"symmetric\"\n");
+        prices.write("triangle/car/2023_TEST.tsv", "A\n1,00\tB\n");
+        prices.write("plaza/2023_TEST.tsv", "Plaza\tCar\nA\t2,00\n");
+        prices.price_service()
+    }
+
+    /// A toll of sections A, B and C having the given matrices.
+    fn toll(matrixes: &[(&str, &str, f64)]) -> Toll {
+        let matrixes = matrixes.iter()
+            .map(|(name, vehicles, price)| format!(r#"{{"friendly_name": "{0}", "matrix_prices": [[0, {2}, {2}], [{2}, 0, {2}], [{2}, {2}, 0]],
+                "permit_id": "", "limit_to_vehicles": [{1}]}}"#, name, vehicles, price))
+            .collect::<Vec<_>>();
+        let sections = ["A", "B", "C"].map(|id| format!(r#"{{"section_id": "{}", "road_local_name": "", "section_local_name": "", "location": [0, 0], "segments": []}}"#, id));
+        serde_json::from_str(&format!(r#"{{"toll_id": "T", "road_local_name": "", "currency": "€", "currency_code": "EUR", "polyline": "", "type": "TOLL",
+            "rules": ["entry_exit_price"], "entry_exit_matrix": [{}], "sections": [{}]}}"#, matrixes.join(", "), sections.join(", "))).unwrap()
+    }
+
+    /// A toll of the plaza A having the given fixed prices.
+    fn fixed_price_toll(fixed_prices: &[(&str, &str, f64)]) -> Toll {
+        let fixed_prices = fixed_prices.iter()
+            .map(|(name, vehicles, price)| format!(r#"{{"friendly_name": "{}", "price": {}, "permit_id": "", "limit_to_vehicles": [{}]}}"#, name, price, vehicles))
+            .collect::<Vec<_>>();
+        serde_json::from_str(&format!(r#"{{"toll_id": "T", "road_local_name": "", "currency": "€", "currency_code": "EUR", "polyline": "", "type": "TOLL",
+            "rules": ["fixed_price"], "entry_exit_matrix": [], "fixed_price": [{}],
+            "sections": [{{"section_id": "A", "road_local_name": "", "section_local_name": "", "location": [0, 0], "segments": []}}]}}"#, fixed_prices.join(", "))).unwrap()
+    }
+
+    const CAR: (&str, &str, f64) = ("Car", r#""PRIVATE", "TAXI", "EV""#, 9.0);
+    const MOTORCYCLE: (&str, &str, f64) = ("Motorcycle", r#""MOTORCYCLE""#, 5.0);
+
+    fn options(missing_price: MissingPricePolicy) -> MatrixOptions {
+        let mut options = MatrixOptions::new(NaiveDate::from_ymd_opt(2023, 6, 1).unwrap());
+        options.missing_price = missing_price;
+        options
+    }
+
+    fn prices(toll: &Toll) -> Vec<(String, Vec<Vec<f64>>)> {
+        toll.entry_exit_matrix.iter()
+            .map(|matrix| (matrix.friendly_name.to_string(), matrix.matrix_prices.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn missing_price_policies() {
+        let price_service = price_service("missing-price");
+        let car = |missing: f64| vec![vec![0.0, 1.0, missing], vec![1.0, 0.0, missing], vec![missing, missing, 0.0]];
+        let filled = |value: f64| vec![vec![0.0, value, value], vec![value, 0.0, value], vec![value, value, 0.0]];
+
+        let mut kept = toll(&[CAR, MOTORCYCLE]);
+        let audit = price_service.update_toll(&mut kept, &options(MissingPricePolicy::KeepExisting));
+        assert!(!audit.failed);
+        assert_eq!(vec![Category::Intermediate, Category::TwoAxleTruck, Category::ThreeAxleTruck], audit.skipped_categories);
+        assert_eq!(vec![("Car".to_string(), car(9.0)), ("Motorcycle".to_string(), filled(5.0))], prices(&kept));
+
+        let mut zero = toll(&[CAR, MOTORCYCLE]);
+        assert!(!price_service.update_toll(&mut zero, &options(MissingPricePolicy::Zero)).failed);
+        assert_eq!(vec![("Car".to_string(), car(0.0)), ("Motorcycle".to_string(), filled(0.0))], prices(&zero));
+
+        let mut sentinel = toll(&[CAR, MOTORCYCLE]);
+        assert!(!price_service.update_toll(&mut sentinel, &options(MissingPricePolicy::Sentinel(-1.0))).failed);
+        assert_eq!(vec![("Car".to_string(), car(-1.0)), ("Motorcycle".to_string(), filled(-1.0))], prices(&sentinel));
+
+        let mut failed = toll(&[CAR, MOTORCYCLE]);
+        assert!(price_service.update_toll(&mut failed, &options(MissingPricePolicy::Fail)).failed);
+        assert_eq!(vec![("Car".to_string(), filled(9.0)), ("Motorcycle".to_string(), filled(5.0))], prices(&failed));
+
+        // only the motorcycle matrix would remain without the prices to keep
+        let mut motorcycle_only = toll(&[MOTORCYCLE]);
+        let audit = price_service.update_toll(&mut motorcycle_only, &options(MissingPricePolicy::KeepExisting));
+        assert!(audit.failed);
+        assert_eq!(4, audit.categories[&Category::Car].not_kept);
+        assert_eq!(vec![("Motorcycle".to_string(), filled(5.0))], prices(&motorcycle_only));
+    }
+
+    fn fixed_prices(toll: &Toll) -> Vec<(String, f64)> {
+        toll.fixed_price.iter()
+            .map(|fixed_price| (fixed_price.friendly_name.to_string(), fixed_price.price))
+            .collect()
+    }
+
+    #[test]
+    fn missing_fixed_price_policies() {
+        let price_service = price_service("missing-fixed-price");
+        const BUS: (&str, &str, f64) = ("Bus", r#""BUS""#, 12.0);
+        let expected = |motorcycle: f64| vec![("Car".to_string(), 2.0), ("Motorcycle".to_string(), motorcycle), ("Bus".to_string(), 12.0)];
+
+        let mut kept = fixed_price_toll(&[CAR, MOTORCYCLE, BUS]);
+        let audit = price_service.update_toll(&mut kept, &options(MissingPricePolicy::KeepExisting));
+        assert!(!audit.failed);
+        assert_eq!(1, audit.fixed_prices[&Category::Motorcycle].kept);
+        assert_eq!(expected(5.0), fixed_prices(&kept));
+
+        let mut zero = fixed_price_toll(&[CAR, MOTORCYCLE, BUS]);
+        assert!(!price_service.update_toll(&mut zero, &options(MissingPricePolicy::Zero)).failed);
+        assert_eq!(expected(0.0), fixed_prices(&zero));
+
+        let mut sentinel = fixed_price_toll(&[CAR, MOTORCYCLE, BUS]);
+        assert!(!price_service.update_toll(&mut sentinel, &options(MissingPricePolicy::Sentinel(-1.0))).failed);
+        assert_eq!(expected(-1.0), fixed_prices(&sentinel));
+
+        let mut failed = fixed_price_toll(&[CAR, MOTORCYCLE, BUS]);
+        assert!(price_service.update_toll(&mut failed, &options(MissingPricePolicy::Fail)).failed);
+        assert_eq!(vec![("Car".to_string(), 9.0), ("Motorcycle".to_string(), 5.0), ("Bus".to_string(), 12.0)], fixed_prices(&failed));
+    }
+
+    #[test]
+    fn diff_currency_errors() {
+        let price_service = price_service("diff-currency");
+        let mut chf = toll(&[CAR]);
+        chf.toll_id = "CHF".to_string();
+        chf.currency_code = "CHF".to_string();
+        let mut invalid = toll(&[CAR]);
+        invalid.toll_id = "INVALID".to_string();
+        invalid.currency_code = "EURO".to_string();
+        let toll_file = std::env::temp_dir().join(format!("waze-toll-tool-diff-currency-{}.json", std::process::id()));
+        std::fs::write(&toll_file, serde_json::json!({"tolls": [chf, invalid]}).to_string()).unwrap();
+        let diffs = price_service.diff_matrix(&toll_file, &options(MissingPricePolicy::KeepExisting)).unwrap();
+        std::fs::remove_file(&toll_file).unwrap();
+
+        assert_eq!(vec!["CHF", "INVALID"], diffs.iter().map(|diff| diff.toll_id.as_str()).collect::<Vec<&str>>());
+        assert_eq!(vec!["Toll left unchanged because of 2 prices without exchange rate to CHF"], diffs[0].errors);
+        assert_eq!(2, diffs[0].currency_mismatches.len());
+        assert_eq!(vec!["Toll skipped : Invalid currency code EURO"], diffs[1].errors);
+    }
+
+    #[test]
+    fn scoped_section_aliases() {
+        let test_prices = TestPrices::new("scoped-section");
+        test_prices.write("alias.csv", "SUD,AIX SUD,AREA\nNORD,AIX NORD,2023_AREA.tsv\n");
+        test_prices.write("triangle/car/2023_AREA.meta.toml", "direction = \"symmetric\"\noperator = \"AREA\"\nnetwork = \"A8\"\n");
+        test_prices.write("triangle/car/2023_AREA.tsv", "Sud\n1,00\tNord\n");
+        let price_service = test_prices.price_service();
+        let toll_on = |road: &str| -> Toll {
+            let sections = ["Sud", "Nord"].map(|id| format!(r#"{{"section_id": "{}", "road_local_name": "", "section_local_name": "", "location": [0, 0], "segments": []}}"#, id));
+            serde_json::from_str(&format!(r#"{{"toll_id": "T", "road_local_name": "{}", "currency": "€", "currency_code": "EUR", "polyline": "", "type": "TOLL",
+                "rules": ["entry_exit_price"], "entry_exit_matrix": [], "sections": [{}]}}"#, road, sections.join(", "))).unwrap()
+        };
+
+        // the operator and file aliases of the price grids of A8 apply to the sections of its tolls
+        assert_eq!(vec!["AIX SUD", "AIX NORD"], price_service.section_ids(&toll_on("A8")));
+        let mut toll = toll_on("A8");
+        assert!(!price_service.update_toll(&mut toll, &options(MissingPricePolicy::KeepExisting)).failed);
+        assert_eq!(vec![("Car".to_string(), vec![vec![0.0, 1.0], vec![1.0, 0.0]])], prices(&toll));
+        // but not to the sections of another motorway
+        assert_eq!(vec!["SUD", "NORD"], price_service.section_ids(&toll_on("A6")));
+    }
+
+    fn price(price: i64) -> Option<Money> {
+        Some(Money::new(price, Currency::EUR))
+    }
+
+    #[test]
+    fn infer_cheapest_sum() {
+        let ids = ["A", "B", "C", "D"].map(|id| id.to_string());
+        let (a_b, b_d, a_c, c_d) = (price(120), price(250), price(200), price(100));
+        let known_prices = vec![
+            vec![None, a_b, a_c, None],
+            vec![None, None, None, b_d],
+            vec![None, None, None, c_d],
+            vec![None, None, None, None]
+        ];
+        assert_eq!(Some((2, Money::new(300, Currency::EUR))), infer_price(&known_prices, &ids, 0, 3));
+        assert_eq!(None, infer_price(&known_prices, &ids, 3, 0));
+    }
+
+    #[test]
+    fn parse_missing_price_policy() {
+        assert_eq!(Some(MissingPricePolicy::KeepExisting), MissingPricePolicy::from_name("Keep"));
+        assert_eq!(Some(MissingPricePolicy::Sentinel(-1.0)), MissingPricePolicy::from_name("-1"));
+        assert_eq!(None, MissingPricePolicy::from_name("skip"));
+    }
+}
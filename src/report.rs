@@ -0,0 +1,21 @@
+use std::{fs, io};
+use std::path::Path;
+use serde::Serialize;
+use crate::matrix_audit::TollAudit;
+use crate::price_grid::PriceLoadAudit;
+
+/// Machine readable report of the commands, written as json.
+#[derive(Serialize)]
+pub struct Report<'a> {
+    pub load: &'a PriceLoadAudit,
+    pub tolls: &'a [TollAudit]
+}
+
+impl Report<'_> {
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json)?;
+        println!("Wrote report {}", path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+use crate::price_paths::PricePaths;
+use crate::price_service::PriceService;
+
+/// A price directory of a test, with an empty alias file, removed when dropped even if the test fails.
+pub(crate) struct TestPrices {
+    root: PathBuf
+}
+
+impl TestPrices {
+    pub(crate) fn new(test: &str) -> TestPrices {
+        let root = std::env::temp_dir().join(format!("waze-toll-tool-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("alias.csv"), "").unwrap();
+        TestPrices {
+            root
+        }
+    }
+
+    /// Write a file below the root, creating its directories.
+    pub(crate) fn write<P: AsRef<Path>>(&self, file: P, content: &str) -> PathBuf {
+        let path = self.root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    pub(crate) fn paths(&self) -> PricePaths {
+        PricePaths::new(&self.root)
+    }
+
+    pub(crate) fn price_service(&self) -> PriceService {
+        PriceService::new(&self.paths()).unwrap()
+    }
+}
+
+impl Drop for TestPrices {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extension of the toll files written next to their source, A89.json -> A89.out.json
+const OUTPUT_EXTENSION: &str = "out.json";
+/// Output of a single toll file when no output is given.
+const DEFAULT_OUTPUT: &str = "out.json";
+
+/// Where the updated toll files are written.
+pub enum TollOutput {
+    /// out.json for a single toll file, next to each source for several
+    Default,
+    /// The output file for a single toll file, else the output directory
+    Path(PathBuf),
+    /// Overwrite each source, after copying it to a .bak backup
+    InPlace
+}
+
+impl TollOutput {
+    /// The output file of a toll file, `batch` telling whether several toll files are processed.
+    pub fn output_file(&self, toll_file: &Path, batch: bool) -> PathBuf {
+        match self {
+            TollOutput::Default if batch => toll_file.with_extension(OUTPUT_EXTENSION),
+            TollOutput::Default => PathBuf::from(DEFAULT_OUTPUT),
+            TollOutput::Path(path) if batch || path.is_dir() => match toll_file.file_name() {
+                Some(file_name) => path.join(file_name),
+                None => path.join(DEFAULT_OUTPUT)
+            },
+            TollOutput::Path(path) => path.to_path_buf(),
+            TollOutput::InPlace => toll_file.to_path_buf()
+        }
+    }
+}
+
+/// The toll files of a file, of the json files of a directory, or of a glob pattern.
+pub fn toll_files(input: &str) -> Result<Vec<PathBuf>, String> {
+    let path = Path::new(input);
+    let mut toll_files = if path.is_dir() {
+        fs::read_dir(path)
+            .map_err(|error| format!("Unable to read directory {} : {}", input, error))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| is_toll_file(path))
+            .collect::<Vec<PathBuf>>()
+    } else if path.is_file() {
+        vec![path.to_path_buf()]
+    } else {
+        glob::glob(input)
+            .map_err(|error| format!("Invalid pattern {} : {}", input, error))?
+            .filter_map(Result::ok)
+            .filter(|path| is_toll_file(path))
+            .collect::<Vec<PathBuf>>()
+    };
+    if toll_files.is_empty() {
+        return Err(format!("No toll file found for {}", input));
+    }
+    toll_files.sort();
+    Ok(toll_files)
+}
+
+/// A json file which is not the output of a previous run.
+fn is_toll_file(path: &Path) -> bool {
+    let file_name = path.file_name().and_then(|file_name| file_name.to_str()).unwrap_or_default();
+    path.is_file()
+        && file_name.ends_with(".json")
+        && file_name != DEFAULT_OUTPUT
+        && !file_name.ends_with(&format!(".{}", OUTPUT_EXTENSION))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use super::{toll_files, TollOutput};
+
+    #[test]
+    fn output_files() {
+        let toll_file = Path::new("tolls/A89.json");
+        assert_eq!(PathBuf::from("out.json"), TollOutput::Default.output_file(toll_file, false));
+        assert_eq!(PathBuf::from("tolls/A89.out.json"), TollOutput::Default.output_file(toll_file, true));
+        assert_eq!(PathBuf::from("updated/A89.json"), TollOutput::Path(PathBuf::from("updated")).output_file(toll_file, true));
+        assert_eq!(PathBuf::from("A89-2024.json"), TollOutput::Path(PathBuf::from("A89-2024.json")).output_file(toll_file, false));
+        assert_eq!(PathBuf::from("tolls/A89.json"), TollOutput::InPlace.output_file(toll_file, false));
+    }
+
+    #[test]
+    fn skip_previous_outputs() {
+        let dir = std::env::temp_dir().join(format!("waze-toll-tool-batch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in ["A89.json", "A89.out.json", "out.json", "notes.txt"] {
+            std::fs::write(dir.join(file), "{}").unwrap();
+        }
+        let expected = Ok(vec![dir.join("A89.json")]);
+        assert_eq!(expected, toll_files(dir.to_str().unwrap()));
+        assert_eq!(expected, toll_files(dir.join("*.json").to_str().unwrap()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
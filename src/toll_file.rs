@@ -1,59 +1,320 @@
 #![allow(non_snake_case)]
 
+use std::{fs, io};
 use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::ops::Range;
 use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
 use serde::de::Error;
-use serde_json::{from_str, Result};
+use serde_json::{from_str, Map, Result, Value};
+use serde_json::ser::PrettyFormatter;
+use crate::json_patch::{indent_value, indentation, line_indent, toll_field_ranges};
 
+const ENTRY_EXIT_MATRIX: &str = "entry_exit_matrix";
+const FIXED_PRICE: &str = "fixed_price";
+
+/// The tolls of a Waze toll file.
 #[derive(Serialize, Deserialize)]
-pub(crate) struct TollFile {
-    pub(crate) tolls: Vec<Toll>
+pub struct TollFile {
+    pub tolls: Vec<Toll>,
+    /// The fields unknown to the tool, written back as they were read
+    #[serde(flatten)]
+    pub extra: Map<String, Value>
 }
 
+/// A toll road and its pricing rules.
 #[derive(Serialize, Deserialize)]
-pub(crate) struct Toll {
-    pub(crate) toll_id: String,
-    road_local_name: String,
-    currency: String,
-    currency_code: String,
-    polyline: String,
-    r#type: String,
-    pub(crate) rules: Vec<String>,
-    pub(crate) entry_exit_matrix: Vec<Matrix>,
-    pub(crate) sections: Vec<Section>
+pub struct Toll {
+    pub toll_id: String,
+    pub road_local_name: String,
+    pub currency: String,
+    pub currency_code: String,
+    pub polyline: String,
+    pub r#type: String,
+    pub rules: Vec<String>,
+    pub entry_exit_matrix: Vec<Matrix>,
+    /// The price of a passage, for the tolls having a fixed price rule
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixed_price: Vec<FixedPrice>,
+    pub sections: Vec<Section>,
+    /// The fields unknown to the tool, written back as they were read
+    #[serde(flatten)]
+    pub extra: Map<String, Value>
 }
 
-#[derive(Serialize, Deserialize)]
-pub(crate) struct Matrix {
-    pub(crate) friendly_name: String,
-    pub(crate) matrix_prices: Vec<Vec<f64>>,
-    pub(crate) permit_id: String,
-    pub(crate) limit_to_vehicles: Vec<String>
+/// The prices of a vehicle category, indexed by entry and exit section.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Matrix {
+    pub friendly_name: String,
+    pub matrix_prices: Vec<Vec<f64>>,
+    pub permit_id: String,
+    pub limit_to_vehicles: Vec<String>,
+    /// The fields unknown to the tool, written back as they were read
+    #[serde(flatten)]
+    pub extra: Map<String, Value>
+}
+
+/// The price of a passage of a vehicle category at the plaza of a toll.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FixedPrice {
+    pub friendly_name: String,
+    pub price: f64,
+    pub permit_id: String,
+    pub limit_to_vehicles: Vec<String>,
+    /// The fields unknown to the tool, written back as they were read
+    #[serde(flatten)]
+    pub extra: Map<String, Value>
+}
+
+/// A Waze toll rule.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RuleType {
+    /// A price for each passage at the plaza of the toll, whatever the entry
+    FixedPrice,
+    /// A price for each entry and exit of the toll
+    EntryExitPrice
 }
 
+impl RuleType {
+    pub fn from_name(name: &str) -> Option<RuleType> {
+        match name {
+            "fixed_price" => Some(RuleType::FixedPrice),
+            "entry_exit_price" => Some(RuleType::EntryExitPrice),
+            _ => None
+        }
+    }
+}
+
+/// How a toll is priced, from its rules.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TollPricing {
+    Fixed,
+    EntryExit,
+    /// Both an entry/exit price and the fixed price of a plaza
+    Mixed
+}
+
+impl TollPricing {
+    pub fn has_entry_exit(&self) -> bool {
+        matches!(self, TollPricing::EntryExit | TollPricing::Mixed)
+    }
+
+    pub fn has_fixed(&self) -> bool {
+        matches!(self, TollPricing::Fixed | TollPricing::Mixed)
+    }
+}
+
+impl Toll {
+    /// The pricing of the toll, an error for a toll without rule or with an unknown rule.
+    pub fn pricing(&self) -> std::result::Result<TollPricing, String> {
+        let mut rule_types = Vec::new();
+        for rule in &self.rules {
+            rule_types.push(RuleType::from_name(rule).ok_or(format!("unknown rule {}", rule))?);
+        }
+        let fixed = rule_types.contains(&RuleType::FixedPrice);
+        let entry_exit = rule_types.contains(&RuleType::EntryExitPrice);
+        match (fixed, entry_exit) {
+            (true, true) => Ok(TollPricing::Mixed),
+            (true, false) => Ok(TollPricing::Fixed),
+            (false, true) => Ok(TollPricing::EntryExit),
+            (false, false) => Err("no rule".to_string())
+        }
+    }
+}
+
+/// An entry or exit of a toll, the section id being the station name.
 #[derive(Serialize, Deserialize)]
-pub(crate) struct Section {
-    pub(crate) section_id: String,
-    road_local_name: String,
-    section_local_name: String,
-    location: [f64; 2],
-    pub(crate) segments: Vec<Segment>
+pub struct Section {
+    pub section_id: String,
+    pub road_local_name: String,
+    pub section_local_name: String,
+    pub location: [f64; 2],
+    pub segments: Vec<Segment>,
+    /// The fields unknown to the tool, written back as they were read
+    #[serde(flatten)]
+    pub extra: Map<String, Value>
 }
 
+/// A Waze segment of a section.
 #[derive(Serialize, Deserialize)]
-pub(crate) struct Segment {
-    permalink: String,
-    id: u64,
-    forwards: bool,
-    fromNode: u64,
-    toNode: u64,
+pub struct Segment {
+    pub permalink: String,
+    pub id: u64,
+    pub forwards: bool,
+    pub fromNode: u64,
+    pub toNode: u64,
+    /// The fields unknown to the tool, written back as they were read
+    #[serde(flatten)]
+    pub extra: Map<String, Value>
+}
+
+/// A toll file with the text it was read from, to write it back changing only its matrices.
+pub struct TollDocument {
+    pub toll_file: TollFile,
+    text: String
+}
+
+impl TollDocument {
+    /// The original text where the prices which changed are replaced, keeping the indentation of the file.
+    /// A changed matrix or fixed price list is written whole, its numbers as serialized by serde (`4` -> `4.0`).
+    fn patched_text(&self) -> std::result::Result<String, String> {
+        let tolls_ranges = toll_field_ranges(&self.text, &[ENTRY_EXIT_MATRIX, FIXED_PRICE])?;
+        if tolls_ranges.len() != self.toll_file.tolls.len() {
+            return Err(format!("found {} tolls in the text instead of {}", tolls_ranges.len(), self.toll_file.tolls.len()));
+        }
+        let indent = indentation(&self.text);
+        let mut edits = Vec::new();
+        for (toll, ranges) in self.toll_file.tolls.iter().zip(tolls_ranges) {
+            let Some(matrix_range) = ranges.get(ENTRY_EXIT_MATRIX) else {
+                continue;
+            };
+            if let Some(json) = self.changed_json(&toll.entry_exit_matrix, matrix_range, indent.as_deref())? {
+                edits.push((matrix_range.clone(), json));
+            }
+            match ranges.get(FIXED_PRICE) {
+                Some(range) => if let Some(json) = self.changed_json(&toll.fixed_price, range, indent.as_deref())? {
+                    edits.push((range.clone(), json));
+                }
+                None if !toll.fixed_price.is_empty() => {
+                    // added after the matrices, on a line of its own when the file is indented
+                    let key_start = self.text[..matrix_range.start].rfind('"')
+                        .and_then(|quote| self.text[..quote].rfind('"'))
+                        .unwrap_or(matrix_range.start);
+                    let separator = match &indent {
+                        Some(_) => format!(",\n{}", line_indent(&self.text, key_start)),
+                        None => ", ".to_string()
+                    };
+                    let json = to_json(&toll.fixed_price, indent.as_deref(), line_indent(&self.text, key_start))?;
+                    edits.push((matrix_range.end..matrix_range.end, format!("{}\"{}\": {}", separator, FIXED_PRICE, json)));
+                }
+                None => {}
+            }
+        }
+        let mut text = self.text.clone();
+        edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+        for (range, json) in edits {
+            text.replace_range(range, &json);
+        }
+        Ok(text)
+    }
+
+    /// The json of a value when it differs from the original text of the range.
+    fn changed_json<T: Serialize + DeserializeOwned>(&self, value: &T, range: &Range<usize>, indent: Option<&str>) -> std::result::Result<Option<String>, String> {
+        let original = from_str::<T>(&self.text[range.clone()])
+            .and_then(serde_json::to_value)
+            .map_err(|error| error.to_string())?;
+        if serde_json::to_value(value).map_err(|error| error.to_string())? == original {
+            return Ok(None);
+        }
+        to_json(value, indent, line_indent(&self.text, range.start)).map(Some)
+    }
+}
+
+/// Serialize a value written after a key of a line having the given indentation.
+fn to_json<T: Serialize>(value: &T, indent: Option<&str>, line_indent: &str) -> std::result::Result<String, String> {
+    match indent {
+        Some(indent) => {
+            let mut buffer = Vec::new();
+            let mut serializer = serde_json::Serializer::with_formatter(&mut buffer, PrettyFormatter::with_indent(indent.as_bytes()));
+            value.serialize(&mut serializer).map_err(|error| error.to_string())?;
+            Ok(indent_value(&String::from_utf8_lossy(&buffer), line_indent))
+        }
+        None => serde_json::to_string(value).map_err(|error| error.to_string())
+    }
 }
 
-pub(crate) fn load_toll_file(toll_file_name: &String) -> Result<TollFile> {
+/// Load a toll file keeping its text.
+pub fn load_toll_document(toll_file_name: &Path) -> Result<TollDocument> {
+    let Ok(text) = read_to_string(toll_file_name) else {
+        return Err(serde_json::Error::custom(format!("Failed to load toll file {}", toll_file_name.display())));
+    };
+    let toll_file = from_str(&text)?;
+    Ok(TollDocument {
+        toll_file,
+        text
+    })
+}
+
+/// Write a toll file as it was read, except for the matrices which changed, rewritten whole.
+pub fn write_toll_document(document: &TollDocument, file_name: &Path) -> io::Result<()> {
+    let text = document.patched_text().map_err(io::Error::other)?;
+    fs::write(file_name, text)?;
+    println!("Wrote {}", file_name.display());
+    Ok(())
+}
+
+/// Load a toll file exported from Waze.
+pub fn load_toll_file(toll_file_name: &Path) -> Result<TollFile> {
     if let Ok(toll_file) = read_to_string(toll_file_name) {
         let toll_file: TollFile = from_str(&toll_file)?;
         Ok(toll_file)
     } else {
-        Err(serde_json::Error::custom(format!("Failed to load toll file {}", toll_file_name)))
+        Err(serde_json::Error::custom(format!("Failed to load toll file {}", toll_file_name.display())))
     }
-}
\ No newline at end of file
+}
+
+/// Copy a toll file to a .bak file before it is overwritten, A89.json -> A89.json.bak
+pub fn backup_toll_file(file_name: &Path) -> io::Result<PathBuf> {
+    let mut backup = file_name.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    fs::copy(file_name, &backup)?;
+    println!("Saved {} to {}", file_name.display(), backup.display());
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json_patch::toll_field_ranges;
+    use super::{Toll, TollDocument, TollPricing, ENTRY_EXIT_MATRIX};
+
+    fn toll(rules: &str) -> Toll {
+        let json = format!("{{\"toll_id\": \"A\", \"road_local_name\": \"A1\", \"currency\": \"€\", \"currency_code\": \"EUR\", \"polyline\": \"\", \"type\": \"TOLL\", \"rules\": {}, \"entry_exit_matrix\": [], \"sections\": []}}", rules);
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn toll_pricing() {
+        assert_eq!(Ok(TollPricing::EntryExit), toll("[\"entry_exit_price\"]").pricing());
+        assert_eq!(Ok(TollPricing::Mixed), toll("[\"fixed_price\", \"entry_exit_price\"]").pricing());
+        assert!(toll("[]").pricing().is_err());
+        assert!(toll("[\"entry_exit_price\", \"unknown\"]").pricing().is_err());
+    }
+
+    #[test]
+    fn patch_changed_matrix_only() {
+        let text = r#"{
+    "version": 2,
+    "tolls": [
+        {
+            "toll_id": "A",
+            "type": "TOLL",
+            "road_local_name": "A1",
+            "custom": {"note": "kept",   "ratio": 1.50},
+            "currency": "€", "currency_code": "EUR", "polyline": "", "rules": ["entry_exit_price"],
+            "entry_exit_matrix": [{"friendly_name": "Car", "matrix_prices": [[0, 4], [4.50, 0]], "permit_id": "", "limit_to_vehicles": ["PRIVATE"]}],
+            "sections": []
+        }
+    ]
+}
+"#;
+        let mut document = TollDocument {
+            toll_file: serde_json::from_str(text).unwrap(),
+            text: text.to_string()
+        };
+        assert_eq!(Ok(text.to_string()), document.patched_text());
+
+        document.toll_file.tolls[0].entry_exit_matrix[0].matrix_prices[0][1] = 5.0;
+        let patched = document.patched_text().unwrap();
+        let range = toll_field_ranges(text, &[ENTRY_EXIT_MATRIX]).unwrap()[0][ENTRY_EXIT_MATRIX].clone();
+        let suffix_start = patched.len() - (text.len() - range.end);
+        assert_eq!(text[..range.start], patched[..range.start]);
+        assert_eq!(text[range.end..], patched[suffix_start..]);
+        // the changed matrix is written whole, with the numbers as serialized by serde
+        let matrix = &patched[range.start..suffix_start];
+        assert!(matrix.contains("\"matrix_prices\": [\n"), "{}", matrix);
+        assert!(matrix.contains("0.0,") && matrix.contains("5.0"));
+        assert!(matrix.contains("4.5"));
+    }
+}